@@ -0,0 +1,381 @@
+use ndarray::{prelude::Array2, Axis};
+use ndarray_rand::rand_distr::{Distribution, Normal};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+// largest value a pixel can have after the normalization done by Dataset::vec_to_array
+const MAX_PIXEL: f32 = 255f32 / 256f32;
+// range of the fraction of the image covered by a random erasing
+const ERASE_AREA: (f32, f32) = (0.02, 0.2);
+// range of the aspect ratio of a random erasing, sampled on a log scale
+const ERASE_ASPECT: (f32, f32) = (0.3, 3.3);
+
+// random transformations applied to each training sample on the fly, so the network sees a
+// slightly different version of every image each epoch
+// every field of 0 disables that transformation
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Augmentation {
+    // largest shift in pixels along each axis
+    pub shift: f32,
+    // largest rotation in degrees, either way
+    pub rotation: f32,
+    // largest relative change in size, 0.1 scales by a factor between 0.9 and 1.1
+    pub scale: f32,
+    // largest displacement in pixels of the elastic distortion
+    pub elastic_alpha: f32,
+    // smoothness of the elastic distortion, standard deviation of the gaussian blur applied to
+    // the random displacement field in pixels
+    pub elastic_sigma: f32,
+    // standard deviation of the gaussian noise added to every pixel
+    pub noise: f32,
+    // probability of erasing a random rectangle of each image to the background
+    pub erase: f32,
+}
+
+impl Default for Augmentation {
+    fn default() -> Self {
+        Augmentation {
+            shift: 0.0,
+            rotation: 0.0,
+            scale: 0.0,
+            elastic_alpha: 0.0,
+            elastic_sigma: 4.0,
+            noise: 0.0,
+            erase: 0.0,
+        }
+    }
+}
+
+impl Augmentation {
+    // check every limit is one the transformations can be sampled from
+    pub fn validate(&self) -> Result<()> {
+        for (name, value) in [
+            ("shift", self.shift),
+            ("rotation", self.rotation),
+            ("scale", self.scale),
+            ("elastic alpha", self.elastic_alpha),
+            ("elastic sigma", self.elastic_sigma),
+            ("noise", self.noise),
+        ] {
+            if !(value >= 0.0 && value.is_finite()) {
+                return Err(Error::InvalidConfig(format!(
+                    "augmentation {} must be at least 0, got {}",
+                    name, value
+                )));
+            }
+        }
+        if self.scale >= 1.0 {
+            return Err(Error::InvalidConfig(format!(
+                "augmentation scale must be below 1, got {}",
+                self.scale
+            )));
+        }
+        if !(0.0..=1.0).contains(&self.erase) {
+            return Err(Error::InvalidConfig(format!(
+                "erase probability must be between 0 and 1, got {}",
+                self.erase
+            )));
+        }
+        Ok(())
+    }
+
+    // whether any transformation is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.warps() || self.noise > 0.0 || self.erase > 0.0
+    }
+
+    // whether pixels have to be moved around, which needs the image to be resampled
+    fn warps(&self) -> bool {
+        self.shift > 0.0 || self.rotation > 0.0 || self.scale > 0.0 || self.elastic_alpha > 0.0
+    }
+
+    // augment every sample of a batch in place
+    // batch: one rows x cols image per column, stored row major
+    pub fn apply(&self, batch: &mut Array2<f32>, rows: usize, cols: usize, rng: &mut impl Rng) {
+        if !self.is_enabled() {
+            return;
+        }
+        for mut column in batch.axis_iter_mut(Axis(1)) {
+            let mut image = column.to_owned().into_shape((rows, cols)).unwrap();
+            if self.warps() {
+                image = self.warp(&image, rng);
+            }
+            if self.noise > 0.0 {
+                let normal = Normal::new(0f32, self.noise).unwrap();
+                image.map_inplace(|x| *x = (*x + normal.sample(rng)).clamp(0f32, MAX_PIXEL));
+            }
+            if self.erase > 0.0 && rng.gen::<f32>() < self.erase {
+                erase(&mut image, rng);
+            }
+            column.assign(&image.into_shape(rows * cols).unwrap());
+        }
+    }
+
+    // resample an image through a random affine transformation around its center combined with
+    // a random smooth elastic displacement
+    fn warp(&self, image: &Array2<f32>, rng: &mut impl Rng) -> Array2<f32> {
+        let (rows, cols) = image.dim();
+        let angle = symmetric(rng, self.rotation).to_radians();
+        let scale = 1f32 + symmetric(rng, self.scale);
+        let (shift_x, shift_y) = (symmetric(rng, self.shift), symmetric(rng, self.shift));
+        let (displacement_x, displacement_y) = if self.elastic_alpha > 0.0 {
+            (
+                self.displacement(rows, cols, rng),
+                self.displacement(rows, cols, rng),
+            )
+        } else {
+            (
+                Array2::<f32>::zeros((rows, cols)),
+                Array2::<f32>::zeros((rows, cols)),
+            )
+        };
+        resample(
+            image,
+            angle,
+            scale,
+            (shift_x, shift_y),
+            (&displacement_x, &displacement_y),
+        )
+    }
+
+    // random displacement for every pixel along one axis, blurred so neighbouring pixels move
+    // together and scaled so the largest displacement is elastic_alpha
+    fn displacement(&self, rows: usize, cols: usize, rng: &mut impl Rng) -> Array2<f32> {
+        let field = Array2::from_shape_fn((rows, cols), |_| rng.gen_range(-1f32..1f32));
+        let smooth = blur(&field, self.elastic_sigma);
+        let largest = smooth.iter().fold(0f32, |max, x| max.max(x.abs()));
+        if largest > 0.0 {
+            smooth * (self.elastic_alpha / largest)
+        } else {
+            smooth
+        }
+    }
+}
+
+// resample an image rotated by angle radians and scaled around its center, then shifted, with
+// every pixel moved by its displacement on top
+// a positive angle turns the image clockwise as it is shown, with rows going down
+fn resample(
+    image: &Array2<f32>,
+    angle: f32,
+    scale: f32,
+    (shift_x, shift_y): (f32, f32),
+    (displacement_x, displacement_y): (&Array2<f32>, &Array2<f32>),
+) -> Array2<f32> {
+    let (rows, cols) = image.dim();
+    let (center_x, center_y) = ((cols - 1) as f32 / 2f32, (rows - 1) as f32 / 2f32);
+    let (sin, cos) = angle.sin_cos();
+    let mut warped = Array2::<f32>::zeros((rows, cols));
+    for ((y, x), value) in warped.indexed_iter_mut() {
+        // map each output pixel back to where it comes from in the input
+        let dx = x as f32 + displacement_x[[y, x]] - center_x - shift_x;
+        let dy = y as f32 + displacement_y[[y, x]] - center_y - shift_y;
+        let source_x = (cos * dx + sin * dy) / scale + center_x;
+        let source_y = (-sin * dx + cos * dy) / scale + center_y;
+        *value = bilinear(image, source_x, source_y);
+    }
+    warped
+}
+
+// uniformly distributed value between -limit and limit
+fn symmetric(rng: &mut impl Rng, limit: f32) -> f32 {
+    if limit > 0.0 {
+        rng.gen_range(-limit..=limit)
+    } else {
+        0f32
+    }
+}
+
+// bilinear interpolation of an image, with the background beyond its borders
+fn bilinear(image: &Array2<f32>, x: f32, y: f32) -> f32 {
+    let (rows, cols) = image.dim();
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let pixel = |x: f32, y: f32| {
+        if x < 0.0 || y < 0.0 || x >= cols as f32 || y >= rows as f32 {
+            0f32
+        } else {
+            image[[y as usize, x as usize]]
+        }
+    };
+    let top = pixel(x0, y0) * (1f32 - fx) + pixel(x0 + 1f32, y0) * fx;
+    let bottom = pixel(x0, y0 + 1f32) * (1f32 - fx) + pixel(x0 + 1f32, y0 + 1f32) * fx;
+    top * (1f32 - fy) + bottom * fy
+}
+
+// separable gaussian blur, treating everything beyond the borders as 0
+fn blur(field: &Array2<f32>, sigma: f32) -> Array2<f32> {
+    if sigma <= 0.0 {
+        return field.clone();
+    }
+    let radius = (3f32 * sigma).ceil() as isize;
+    let kernel: Vec<f32> = (-radius..=radius)
+        .map(|i| (-(i * i) as f32 / (2f32 * sigma * sigma)).exp())
+        .collect();
+    let (rows, cols) = field.dim();
+    let mut horizontal = Array2::<f32>::zeros((rows, cols));
+    for ((y, x), value) in horizontal.indexed_iter_mut() {
+        for (k, weight) in kernel.iter().enumerate() {
+            let source = x as isize + k as isize - radius;
+            if (0..cols as isize).contains(&source) {
+                *value += weight * field[[y, source as usize]];
+            }
+        }
+    }
+    let mut blurred = Array2::<f32>::zeros((rows, cols));
+    for ((y, x), value) in blurred.indexed_iter_mut() {
+        for (k, weight) in kernel.iter().enumerate() {
+            let source = y as isize + k as isize - radius;
+            if (0..rows as isize).contains(&source) {
+                *value += weight * horizontal[[source as usize, x]];
+            }
+        }
+    }
+    blurred
+}
+
+// set a random rectangle of the image to the background
+fn erase(image: &mut Array2<f32>, rng: &mut impl Rng) {
+    let (rows, cols) = image.dim();
+    let area = rng.gen_range(ERASE_AREA.0..ERASE_AREA.1) * (rows * cols) as f32;
+    let aspect = rng
+        .gen_range(ERASE_ASPECT.0.ln()..ERASE_ASPECT.1.ln())
+        .exp();
+    let height = ((area * aspect).sqrt().round() as usize).clamp(1, rows);
+    let width = ((area / aspect).sqrt().round() as usize).clamp(1, cols);
+    let top = rng.gen_range(0..=rows - height);
+    let left = rng.gen_range(0..=cols - width);
+    for y in top..top + height {
+        for x in left..left + width {
+            image[[y, x]] = 0f32;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray_rand::rand::{rngs::StdRng, SeedableRng};
+
+    use crate::gradient_check::random;
+
+    fn batch() -> Array2<f32> {
+        random((8 * 6, 5), 1).map(|x| x.abs() * MAX_PIXEL)
+    }
+
+    #[test]
+    fn disabled_augmentation_leaves_batch_unchanged() {
+        let mut augmented = batch();
+        Augmentation::default().apply(&mut augmented, 8, 6, &mut StdRng::seed_from_u64(2));
+        assert_eq!(augmented, batch());
+    }
+
+    #[test]
+    fn zero_transformation_keeps_image() {
+        // with every limit this small, the warp is practically the identity
+        let augmentation = Augmentation {
+            shift: 1e-6,
+            ..Augmentation::default()
+        };
+        let mut augmented = batch();
+        augmentation.apply(&mut augmented, 8, 6, &mut StdRng::seed_from_u64(3));
+        for (a, b) in augmented.iter().zip(batch().iter()) {
+            assert!((a - b).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn augmented_pixels_stay_in_range() {
+        let augmentation = Augmentation {
+            shift: 2.0,
+            rotation: 15.0,
+            scale: 0.1,
+            elastic_alpha: 2.0,
+            elastic_sigma: 2.0,
+            noise: 0.3,
+            erase: 1.0,
+        };
+        let mut augmented = batch();
+        augmentation.apply(&mut augmented, 8, 6, &mut StdRng::seed_from_u64(4));
+        assert_eq!(augmented.dim(), batch().dim());
+        assert!(augmented.iter().all(|x| (0f32..=MAX_PIXEL).contains(x)));
+        assert_ne!(augmented, batch());
+    }
+
+    // image with the given pixels lit, at (row, column)
+    fn lit(rows: usize, cols: usize, pixels: &[(usize, usize)]) -> Array2<f32> {
+        let mut image = Array2::zeros((rows, cols));
+        for &pixel in pixels {
+            image[pixel] = 1f32;
+        }
+        image
+    }
+
+    #[test]
+    fn whole_pixel_shift_moves_pixel_exactly() {
+        let still = Array2::zeros((8, 6));
+        let shifted = resample(
+            &lit(8, 6, &[(2, 1)]),
+            0.0,
+            1.0,
+            (2.0, 3.0),
+            (&still, &still),
+        );
+        // 2 pixels right and 3 pixels down
+        assert_eq!(shifted, lit(8, 6, &[(5, 3)]));
+    }
+
+    #[test]
+    fn rotation_turns_clockwise() {
+        let still = Array2::zeros((5, 5));
+        // an L with its corner at the top right of the center column
+        let image = lit(5, 5, &[(0, 2), (1, 2), (0, 3)]);
+        let rotated = resample(
+            &image,
+            90f32.to_radians(),
+            1.0,
+            (0.0, 0.0),
+            (&still, &still),
+        );
+        // a quarter turn clockwise brings the top to the right and the right to the bottom
+        let expected = lit(5, 5, &[(2, 4), (2, 3), (3, 4)]);
+        for (a, b) in rotated.iter().zip(expected.iter()) {
+            assert!((a - b).abs() < 1e-4, "{:?}", rotated);
+        }
+    }
+
+    #[test]
+    fn erasing_clears_one_rectangle_of_bounded_area() {
+        let (rows, cols) = (28, 28);
+        let augmentation = Augmentation {
+            erase: 1.0,
+            ..Augmentation::default()
+        };
+        let mut augmented = Array2::from_elem((rows * cols, 20), MAX_PIXEL);
+        augmentation.apply(&mut augmented, rows, cols, &mut StdRng::seed_from_u64(5));
+        for column in augmented.columns() {
+            let image = column.to_owned().into_shape((rows, cols)).unwrap();
+            let erased: Vec<(usize, usize)> = image
+                .indexed_iter()
+                .filter(|(_, &value)| value == 0.0)
+                .map(|(pixel, _)| pixel)
+                .collect();
+            let top = erased.iter().map(|&(y, _)| y).min().unwrap();
+            let bottom = erased.iter().map(|&(y, _)| y).max().unwrap();
+            let left = erased.iter().map(|&(_, x)| x).min().unwrap();
+            let right = erased.iter().map(|&(_, x)| x).max().unwrap();
+            let (height, width) = (bottom - top + 1, right - left + 1);
+            // every pixel of the bounding box is erased, and nothing else
+            assert_eq!(erased.len(), height * width);
+            // rounding each side to whole pixels moves the area by at most half a pixel per side
+            let pixels = (rows * cols) as f32;
+            let slack = (height + width) as f32 / 2f32 + 1f32;
+            let area = erased.len() as f32;
+            assert!(area >= ERASE_AREA.0 * pixels - slack, "{}", area);
+            assert!(area <= ERASE_AREA.1 * pixels + slack, "{}", area);
+        }
+    }
+}
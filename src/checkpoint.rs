@@ -0,0 +1,306 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use ndarray::prelude::Array2;
+use ndarray_rand::rand::rngs::mock::StepRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::layers::{
+    batchnorm::BatchNorm, conv2d::Conv2d, dropout::Dropout, elu::ELU, gelu::GELU,
+    initializer::Initializer, layer::ActivationLayer, maxpool2d::MaxPool2d, prelu::PReLU,
+    relu::ReLU, sigmoid::Sigmoid, silu::SiLU, softmax::Softmax, tanh::Tanh,
+};
+use crate::metrics::Metrics;
+use crate::model::{Hyperparameters, TrainingRun};
+use crate::optimizer::{Optimizer, OptimizerState};
+use crate::scheduler::Scheduler;
+
+// version of the checkpoint format, bumped whenever a saved file would no longer load correctly
+pub const CHECKPOINT_VERSION: u32 = 3;
+
+// description of a single layer, enough to rebuild it before loading its parameters
+// layers with weights record the initializer their weights were drawn with when created
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum LayerSpec {
+    ReLU {
+        input: usize,
+        nodes: usize,
+        relu_coefficient: f32,
+        initializer: Initializer,
+    },
+    Softmax {
+        input: usize,
+        nodes: usize,
+        initializer: Initializer,
+    },
+    Conv2d {
+        channels: usize,
+        height: usize,
+        width: usize,
+        filters: usize,
+        kernel: usize,
+        padding: usize,
+        relu_coefficient: f32,
+        initializer: Initializer,
+    },
+    MaxPool2d {
+        channels: usize,
+        height: usize,
+        width: usize,
+        size: usize,
+    },
+    Dropout {
+        nodes: usize,
+        rate: f32,
+    },
+    BatchNorm {
+        channels: usize,
+        size: usize,
+        momentum: f32,
+    },
+    Sigmoid {
+        input: usize,
+        nodes: usize,
+        initializer: Initializer,
+    },
+    Tanh {
+        input: usize,
+        nodes: usize,
+        initializer: Initializer,
+    },
+    GELU {
+        input: usize,
+        nodes: usize,
+        initializer: Initializer,
+    },
+    SiLU {
+        input: usize,
+        nodes: usize,
+        initializer: Initializer,
+    },
+    ELU {
+        input: usize,
+        nodes: usize,
+        elu_coefficient: f32,
+        initializer: Initializer,
+    },
+    PReLU {
+        input: usize,
+        nodes: usize,
+        initializer: Initializer,
+    },
+}
+
+impl LayerSpec {
+    // amount of inputs each sample needs to have for this layer
+    pub fn input_size(&self) -> usize {
+        match *self {
+            LayerSpec::ReLU { input, .. }
+            | LayerSpec::Softmax { input, .. }
+            | LayerSpec::Sigmoid { input, .. }
+            | LayerSpec::Tanh { input, .. }
+            | LayerSpec::GELU { input, .. }
+            | LayerSpec::SiLU { input, .. }
+            | LayerSpec::ELU { input, .. }
+            | LayerSpec::PReLU { input, .. } => input,
+            LayerSpec::Conv2d {
+                channels,
+                height,
+                width,
+                ..
+            }
+            | LayerSpec::MaxPool2d {
+                channels,
+                height,
+                width,
+                ..
+            } => channels * height * width,
+            LayerSpec::Dropout { nodes, .. } => nodes,
+            LayerSpec::BatchNorm { channels, size, .. } => channels * size,
+        }
+    }
+
+    // how the weights of the layer were drawn, for layers that have weights
+    pub fn initializer(&self) -> Option<Initializer> {
+        match *self {
+            LayerSpec::ReLU { initializer, .. }
+            | LayerSpec::Softmax { initializer, .. }
+            | LayerSpec::Conv2d { initializer, .. }
+            | LayerSpec::Sigmoid { initializer, .. }
+            | LayerSpec::Tanh { initializer, .. }
+            | LayerSpec::GELU { initializer, .. }
+            | LayerSpec::SiLU { initializer, .. }
+            | LayerSpec::ELU { initializer, .. }
+            | LayerSpec::PReLU { initializer, .. } => Some(initializer),
+            LayerSpec::MaxPool2d { .. }
+            | LayerSpec::Dropout { .. }
+            | LayerSpec::BatchNorm { .. } => None,
+        }
+    }
+
+    // create a layer matching this description, with every weight 0 since the parameters of a
+    // checkpoint are loaded into it right after
+    pub fn build(&self) -> Result<Box<dyn ActivationLayer>> {
+        // zeros draws nothing, so the rng is only there to satisfy the constructors
+        let (zeros, rng) = (Initializer::Zeros, &mut StepRng::new(0, 0));
+        let mut layer: Box<dyn ActivationLayer> = match *self {
+            LayerSpec::ReLU {
+                input,
+                nodes,
+                relu_coefficient,
+                ..
+            } => Box::new(ReLU::new(input, nodes, relu_coefficient, zeros, rng)?),
+            LayerSpec::Softmax { input, nodes, .. } => {
+                Box::new(Softmax::new(input, nodes, zeros, rng)?)
+            }
+            LayerSpec::Conv2d {
+                channels,
+                height,
+                width,
+                filters,
+                kernel,
+                padding,
+                relu_coefficient,
+                ..
+            } => Box::new(Conv2d::new(
+                channels,
+                height,
+                width,
+                filters,
+                kernel,
+                padding,
+                relu_coefficient,
+                zeros,
+                rng,
+            )?),
+            LayerSpec::MaxPool2d {
+                channels,
+                height,
+                width,
+                size,
+            } => Box::new(MaxPool2d::new(channels, height, width, size)?),
+            LayerSpec::Dropout { nodes, rate } => Box::new(Dropout::new(nodes, rate)?),
+            LayerSpec::BatchNorm {
+                channels,
+                size,
+                momentum,
+            } => Box::new(BatchNorm::new(channels, size, momentum)?),
+            LayerSpec::Sigmoid { input, nodes, .. } => {
+                Box::new(Sigmoid::new(input, nodes, zeros, rng)?)
+            }
+            LayerSpec::Tanh { input, nodes, .. } => Box::new(Tanh::new(input, nodes, zeros, rng)?),
+            LayerSpec::GELU { input, nodes, .. } => Box::new(GELU::new(input, nodes, zeros, rng)?),
+            LayerSpec::SiLU { input, nodes, .. } => Box::new(SiLU::new(input, nodes, zeros, rng)?),
+            LayerSpec::ELU {
+                input,
+                nodes,
+                elu_coefficient,
+                ..
+            } => Box::new(ELU::new(input, nodes, elu_coefficient, zeros, rng)?),
+            LayerSpec::PReLU { input, nodes, .. } => {
+                Box::new(PReLU::new(input, nodes, zeros, rng)?)
+            }
+        };
+        // the weights are loaded right after, but the layer still describes how they started
+        if let Some(initializer) = self.initializer() {
+            layer.layer_mut().initializer = initializer;
+        }
+        Ok(layer)
+    }
+}
+
+// architecture and learned parameters of a single layer
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LayerCheckpoint {
+    pub spec: LayerSpec,
+    pub weights: Array2<f32>,
+    pub biases: Array2<f32>,
+    // tensors kept besides the weights and biases, like the running statistics of batch
+    // normalization or the learned slopes of prelu, empty for most layers and missing from older
+    // checkpoints
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub buffers: Vec<Array2<f32>>,
+}
+
+// everything needed to recreate a trained model
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    // format version, checked on load
+    pub version: u32,
+    // hyperparameters the model was created with
+    pub hyperparameters: Hyperparameters,
+    // amount of epochs the model has been trained for
+    pub epoch: usize,
+    // every layer in order of forward propogation
+    pub layers: Vec<LayerCheckpoint>,
+    // state of training when the checkpoint was saved before training finished, missing once
+    // it has
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub training: Option<TrainingState>,
+}
+
+// everything besides the parameters needed to continue an unfinished run of training exactly
+// where it stopped
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TrainingState {
+    // epoch the run stops at, the batches left in the current epoch and early stopping progress
+    pub run: TrainingRun,
+    // optimizer with its step count, and the scheduler with its plateau progress
+    pub optimizer: Optimizer,
+    pub scheduler: Scheduler,
+    // optimizer state of every parameter, one list per layer in order of forward propogation
+    pub optimizer_states: Vec<Vec<OptimizerState>>,
+    // generator every remaining random number of the run is drawn from
+    pub rng: ChaCha8Rng,
+    // loss and metrics tallied over the batches of the current epoch so far
+    pub loss_sum: f32,
+    pub loss_samples: f32,
+    pub metrics: Metrics,
+    // gradient norms of the batches so far, weighted by their samples like the loss
+    #[serde(default)]
+    pub gradient_norm_sum: f32,
+}
+
+impl Checkpoint {
+    // write the checkpoint to a file as json
+    // the file is written next to the path first and then moved over it, so being interrupted
+    // while saving never leaves a partially written checkpoint behind
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+        let partial = Path::new(&partial);
+        let mut writer =
+            BufWriter::new(File::create(partial).map_err(|err| Error::io(partial, err))?);
+        serde_json::to_writer(&mut writer, self)
+            .map_err(io::Error::from)
+            .and_then(|_| writer.flush())
+            .map_err(|err| Error::io(partial, err))?;
+        drop(writer);
+        fs::rename(partial, path).map_err(|err| Error::io(path, err))
+    }
+
+    // read a checkpoint from a file, rejecting files written by another format version
+    pub fn load(path: &Path) -> Result<Checkpoint> {
+        let reader = BufReader::new(File::open(path).map_err(|err| Error::io(path, err))?);
+        let checkpoint: Checkpoint = serde_json::from_reader(reader).map_err(|err| {
+            if err.is_io() {
+                Error::io(path, err.into())
+            } else {
+                Error::invalid_file(path, format!("not a valid checkpoint: {}", err))
+            }
+        })?;
+        if checkpoint.version != CHECKPOINT_VERSION {
+            return Err(Error::invalid_file(
+                path,
+                format!(
+                    "checkpoint version {} is not supported, expected version {}",
+                    checkpoint.version, CHECKPOINT_VERSION
+                ),
+            ));
+        }
+        Ok(checkpoint)
+    }
+}
@@ -0,0 +1,62 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// everything that can go wrong while building, loading, training or running a model
+#[derive(Debug)]
+pub enum Error {
+    // a file could not be opened, read or written
+    Io { path: PathBuf, source: io::Error },
+    // a file was read but does not hold what it should, e.g. a truncated idx file
+    InvalidFile { path: PathBuf, message: String },
+    // hyperparameters or layer sizes that no model can be built with
+    InvalidConfig(String),
+    // data or parameters whose shape does not fit the network they are given to
+    ShapeMismatch(String),
+    // the training loss stopped being a finite number
+    Diverged { epoch: usize },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub(crate) fn io(path: &Path, source: io::Error) -> Error {
+        Error::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    pub(crate) fn invalid_file(path: &Path, message: impl fmt::Display) -> Error {
+        Error::InvalidFile {
+            path: path.to_path_buf(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::InvalidFile { path, message } => write!(f, "{}: {}", path.display(), message),
+            Error::InvalidConfig(message) | Error::ShapeMismatch(message) => {
+                write!(f, "{}", message)
+            }
+            Error::Diverged { epoch } => write!(
+                f,
+                "training diverged in epoch {}, the loss is no longer finite, try a lower learning rate",
+                epoch
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
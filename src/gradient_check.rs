@@ -0,0 +1,122 @@
+use ndarray::{indices, prelude::Array2, Array};
+use ndarray_rand::rand::{rngs::StdRng, SeedableRng};
+use ndarray_rand::rand_distr::Uniform;
+use ndarray_rand::RandomExt;
+
+use crate::layers::layer::{ActivationLayer, Layer};
+use crate::loss::Loss;
+
+// step used for the central differences, small enough to rarely cross a relu kink or change the
+// max of a pooling window, large enough for f32 rounding not to drown out the difference
+const STEP: f32 = 1e-3;
+// largest allowed difference between an analytic and a numeric derivative, relative to the
+// larger of the two for derivatives above 1
+const TOLERANCE: f32 = 1e-2;
+
+// uniformly distributed values between -1 and 1, the same every run for a given seed
+pub fn random(shape: (usize, usize), seed: u64) -> Array2<f32> {
+    Array::random_using(
+        shape,
+        Uniform::new(-1f32, 1f32),
+        &mut StdRng::seed_from_u64(seed),
+    )
+}
+
+// compare the gradients a layer calculates for its weights, biases and input to central
+// differences, panicking on the first one that does not match
+// the loss is sum(upstream * output) for a random upstream gradient, so the layer is checked
+// against any gradient it could receive rather than one particular loss
+pub fn check_layer(layer: &mut dyn ActivationLayer, input: &Array2<f32>, seed: u64) {
+    let input = Layer::dummy_layer(input.clone());
+    let samples = input.layer.ncols() as f32;
+    layer.forward_prop(&input);
+    let upstream = random(layer.layer().layer.dim(), seed);
+    layer.backward_prop(&Layer::dummy_layer(upstream.clone()), &input);
+    // layers average their parameter gradients over the batch, the input gradient is per sample
+    let d_weights = &layer.layer().d_weights * samples;
+    let d_biases = &layer.layer().d_biases * samples;
+    let d_input = layer.input_gradient();
+
+    let objective = |layer: &mut dyn ActivationLayer, input: &Layer| {
+        layer.forward_prop(input);
+        (&layer.layer().layer * &upstream).sum()
+    };
+
+    for index in indices(d_weights.dim()) {
+        let original = layer.layer().weights[index];
+        layer.layer_mut().weights[index] = original + STEP;
+        let plus = objective(layer, &input);
+        layer.layer_mut().weights[index] = original - STEP;
+        let minus = objective(layer, &input);
+        layer.layer_mut().weights[index] = original;
+        assert_close(
+            "weight",
+            index,
+            d_weights[index],
+            (plus - minus) / (2f32 * STEP),
+        );
+    }
+    for index in indices(d_biases.dim()) {
+        let original = layer.layer().biases[index];
+        layer.layer_mut().biases[index] = original + STEP;
+        let plus = objective(layer, &input);
+        layer.layer_mut().biases[index] = original - STEP;
+        let minus = objective(layer, &input);
+        layer.layer_mut().biases[index] = original;
+        assert_close(
+            "bias",
+            index,
+            d_biases[index],
+            (plus - minus) / (2f32 * STEP),
+        );
+    }
+    assert_eq!(
+        d_input.dim(),
+        input.layer.dim(),
+        "input gradient has the wrong shape"
+    );
+    let mut perturbed = input.clone();
+    for index in indices(d_input.dim()) {
+        let original = input.layer[index];
+        perturbed.layer[index] = original + STEP;
+        let plus = objective(layer, &perturbed);
+        perturbed.layer[index] = original - STEP;
+        let minus = objective(layer, &perturbed);
+        perturbed.layer[index] = original;
+        assert_close(
+            "input",
+            index,
+            d_input[index],
+            (plus - minus) / (2f32 * STEP),
+        );
+    }
+}
+
+// compare the gradient of a loss with respect to the network output to central differences
+pub fn check_loss(loss: Loss, output: &Array2<f32>, targets: &Array2<f32>) {
+    // the loss is averaged over the batch while its gradient is per sample
+    let samples = output.ncols() as f32;
+    let gradient = loss.gradient(output, targets);
+    let mut perturbed = output.clone();
+    for index in indices(output.dim()) {
+        perturbed[index] = output[index] + STEP;
+        let plus = loss.loss(&perturbed, targets);
+        perturbed[index] = output[index] - STEP;
+        let minus = loss.loss(&perturbed, targets);
+        perturbed[index] = output[index];
+        let numeric = samples * (plus - minus) / (2f32 * STEP);
+        assert_close("output", index, gradient[index], numeric);
+    }
+}
+
+fn assert_close(name: &str, index: (usize, usize), analytic: f32, numeric: f32) {
+    let error = (analytic - numeric).abs() / analytic.abs().max(numeric.abs()).max(1f32);
+    assert!(
+        error <= TOLERANCE,
+        "{} gradient at {:?} is {}, but the numeric gradient is {}",
+        name,
+        index,
+        analytic,
+        numeric
+    );
+}
@@ -0,0 +1,389 @@
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use ndarray::{Array1, Array2};
+
+use crate::error::{Error, Result};
+
+// width and height of the images the network is trained on
+pub const IMAGE_SIZE: usize = 28;
+// pixels at or below this intensity are treated as background when cropping
+const INK_THRESHOLD: f32 = 0.1;
+
+// how the images of a dataset were fit into their frame, see DatasetKind::framing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Framing {
+    // width and height of the box the longest side of the content is scaled to
+    pub size: usize,
+    // center the content by its center of mass instead of the middle of its bounding box
+    pub center_of_mass: bool,
+}
+
+// grayscale image with intensities between 0 (black) and 1 (white), stored row major
+pub struct GrayImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<f32>,
+}
+
+impl GrayImage {
+    fn get(&self, x: usize, y: usize) -> f32 {
+        self.pixels[y * self.width + x]
+    }
+}
+
+// read a png or pgm file, picking the decoder from the file's magic number
+pub fn read_image(path: &Path) -> Result<GrayImage> {
+    let mut bytes = Vec::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .map_err(|err| Error::io(path, err))?;
+    if bytes.starts_with(b"\x89PNG") {
+        read_png(path, &bytes)
+    } else if bytes.starts_with(b"P2") || bytes.starts_with(b"P5") {
+        read_pgm(path, &bytes)
+    } else {
+        Err(Error::invalid_file(path, "not a png or pgm file"))
+    }
+}
+
+// decode a png, converting color to luminance and compositing transparency onto white
+fn read_png(path: &Path, bytes: &[u8]) -> Result<GrayImage> {
+    let mut decoder = png::Decoder::new(BufReader::new(bytes));
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder
+        .read_info()
+        .map_err(|err| Error::invalid_file(path, format!("could not decode png: {}", err)))?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buffer)
+        .map_err(|err| Error::invalid_file(path, format!("could not decode png: {}", err)))?;
+    let channels = info.color_type.samples();
+    let pixels = buffer[..info.buffer_size()]
+        .chunks(channels)
+        .map(|pixel| {
+            let value = |i: usize| pixel[i] as f32 / 255f32;
+            let (luminance, alpha) = match info.color_type {
+                png::ColorType::Grayscale => (value(0), 1f32),
+                png::ColorType::GrayscaleAlpha => (value(0), value(1)),
+                png::ColorType::Rgb => (luma(value(0), value(1), value(2)), 1f32),
+                png::ColorType::Rgba => (luma(value(0), value(1), value(2)), value(3)),
+                // indexed images are expanded to rgb by the decoder
+                png::ColorType::Indexed => (value(0), 1f32),
+            };
+            luminance * alpha + (1f32 - alpha)
+        })
+        .collect();
+    Ok(GrayImage {
+        width: info.width as usize,
+        height: info.height as usize,
+        pixels,
+    })
+}
+
+// rec. 601 luma, the same weighting used by most grayscale conversions
+fn luma(r: f32, g: f32, b: f32) -> f32 {
+    0.299 * r + 0.587 * g + 0.114 * b
+}
+
+// decode an ascii (P2) or binary (P5) pgm file
+fn read_pgm(path: &Path, bytes: &[u8]) -> Result<GrayImage> {
+    let binary = bytes.starts_with(b"P5");
+    // header is the magic number, width, height and max value, separated by whitespace and
+    // possibly interleaved with comments
+    let mut position = 2;
+    let mut header = [0usize; 3];
+    for value in header.iter_mut() {
+        *value = pgm_token(path, bytes, &mut position)?
+            .parse()
+            .map_err(|_| Error::invalid_file(path, "malformed pgm header"))?;
+    }
+    let [width, height, max_value] = header;
+    if width == 0 || height == 0 || max_value == 0 || max_value > u16::MAX as usize {
+        return Err(Error::invalid_file(path, "malformed pgm header"));
+    }
+    // every pixel takes at least a byte, so a header describing more pixels than the file has
+    // bytes left is rejected before anything is allocated for them
+    let too_short = || Error::invalid_file(path, "pgm file is shorter than its header describes");
+    let count = width
+        .checked_mul(height)
+        .filter(|count| *count <= bytes.len() - position)
+        .ok_or_else(too_short)?;
+    let samples: Vec<usize> = if binary {
+        // a single whitespace character separates the header from the pixels
+        let start = position + 1;
+        let size = if max_value > 255 { 2 } else { 1 };
+        let data = bytes
+            .get(start..start + count * size)
+            .ok_or_else(too_short)?;
+        if size == 2 {
+            data.chunks(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]) as usize)
+                .collect()
+        } else {
+            data.iter().map(|byte| *byte as usize).collect()
+        }
+    } else {
+        let mut samples = Vec::with_capacity(count);
+        for _ in 0..count {
+            samples.push(
+                pgm_token(path, bytes, &mut position)?
+                    .parse()
+                    .map_err(|_| Error::invalid_file(path, "malformed pgm pixel"))?,
+            );
+        }
+        samples
+    };
+    Ok(GrayImage {
+        width,
+        height,
+        pixels: samples
+            .iter()
+            .map(|sample| (*sample).min(max_value) as f32 / max_value as f32)
+            .collect(),
+    })
+}
+
+// next whitespace separated token of a pgm file, skipping comments
+fn pgm_token<'a>(path: &Path, bytes: &'a [u8], position: &mut usize) -> Result<&'a str> {
+    loop {
+        match bytes.get(*position) {
+            Some(b'#') => {
+                while !matches!(bytes.get(*position), Some(b'\n') | None) {
+                    *position += 1;
+                }
+            }
+            Some(byte) if byte.is_ascii_whitespace() => *position += 1,
+            Some(_) => break,
+            None => return Err(Error::invalid_file(path, "pgm file ended unexpectedly")),
+        }
+    }
+    let start = *position;
+    while matches!(bytes.get(*position), Some(byte) if !byte.is_ascii_whitespace()) {
+        *position += 1;
+    }
+    std::str::from_utf8(&bytes[start..*position])
+        .map_err(|_| Error::invalid_file(path, "malformed pgm file"))
+}
+
+// convert any image into a column in the same format as the dataset, processed the same way its
+// images were: light on dark, scaled to fit the box of the framing and centered in a 28x28 image,
+// with pixels normalized like Dataset::vec_to_array
+pub fn to_column(image: &GrayImage, framing: Framing) -> Array1<f32> {
+    // mnist digits are light on a dark background, so invert images with a light border
+    let mut border = 0f32;
+    for x in 0..image.width {
+        border += image.get(x, 0) + image.get(x, image.height - 1);
+    }
+    for y in 0..image.height {
+        border += image.get(0, y) + image.get(image.width - 1, y);
+    }
+    let invert = border / (2 * (image.width + image.height)) as f32 > 0.5;
+    let ink = |x: usize, y: usize| {
+        let value = image.get(x, y);
+        if invert {
+            1f32 - value
+        } else {
+            value
+        }
+    };
+
+    // crop to the bounding box of the digit
+    let (mut left, mut top, mut right, mut bottom) = (image.width, image.height, 0, 0);
+    for y in 0..image.height {
+        for x in 0..image.width {
+            if ink(x, y) > INK_THRESHOLD {
+                left = left.min(x);
+                top = top.min(y);
+                right = right.max(x + 1);
+                bottom = bottom.max(y + 1);
+            }
+        }
+    }
+    let mut out = Array2::<f32>::zeros((IMAGE_SIZE, IMAGE_SIZE));
+    if left >= right {
+        // blank image, nothing to center
+        return out.into_shape(IMAGE_SIZE * IMAGE_SIZE).unwrap();
+    }
+
+    // scale the longest side of the digit to the box, keeping its aspect ratio
+    let (crop_width, crop_height) = (right - left, bottom - top);
+    let size = framing.size.min(IMAGE_SIZE);
+    let scale = size as f32 / crop_width.max(crop_height) as f32;
+    let width = ((crop_width as f32 * scale).round() as usize).clamp(1, size);
+    let height = ((crop_height as f32 * scale).round() as usize).clamp(1, size);
+    let mut digit = Array2::<f32>::zeros((height, width));
+    // average a grid of samples inside each target pixel, so downscaling does not alias
+    const SUBSAMPLES: usize = 4;
+    for ((y, x), value) in digit.indexed_iter_mut() {
+        let mut sum = 0f32;
+        for sy in 0..SUBSAMPLES {
+            for sx in 0..SUBSAMPLES {
+                let source_x = left as f32
+                    + (x as f32 + (sx as f32 + 0.5) / SUBSAMPLES as f32) * crop_width as f32
+                        / width as f32;
+                let source_y = top as f32
+                    + (y as f32 + (sy as f32 + 0.5) / SUBSAMPLES as f32) * crop_height as f32
+                        / height as f32;
+                sum += bilinear(&ink, image, source_x - 0.5, source_y - 0.5);
+            }
+        }
+        *value = sum / (SUBSAMPLES * SUBSAMPLES) as f32;
+    }
+
+    // shift the digit so its center of mass, or the middle of the box, lands in the middle of the
+    // image
+    let (mut mass, mut mass_x, mut mass_y) = (0f32, 0f32, 0f32);
+    for ((y, x), value) in digit.indexed_iter() {
+        mass += value;
+        mass_x += x as f32 * value;
+        mass_y += y as f32 * value;
+    }
+    let center = |total: f32, size: usize| {
+        let middle = if framing.center_of_mass {
+            total / mass
+        } else {
+            (size as f32 - 1f32) / 2f32
+        };
+        let offset = (IMAGE_SIZE as f32 / 2f32 - 0.5 - middle).round() as isize;
+        offset.clamp(0, (IMAGE_SIZE - size) as isize) as usize
+    };
+    let (offset_x, offset_y) = (center(mass_x, width), center(mass_y, height));
+    for ((y, x), value) in digit.indexed_iter() {
+        // quantize to bytes and normalize the same way as the dataset
+        out[[y + offset_y, x + offset_x]] = (value.clamp(0f32, 1f32) * 255f32).round() / 256f32;
+    }
+    out.into_shape(IMAGE_SIZE * IMAGE_SIZE).unwrap()
+}
+
+// bilinear interpolation of a pixel function, clamping coordinates to the image
+fn bilinear(pixel: &impl Fn(usize, usize) -> f32, image: &GrayImage, x: f32, y: f32) -> f32 {
+    let x = x.clamp(0f32, (image.width - 1) as f32);
+    let y = y.clamp(0f32, (image.height - 1) as f32);
+    let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+    let (x1, y1) = (
+        (x0 + 1).min(image.width - 1),
+        (y0 + 1).min(image.height - 1),
+    );
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+    let top = pixel(x0, y0) * (1f32 - fx) + pixel(x1, y0) * fx;
+    let bottom = pixel(x0, y1) * (1f32 - fx) + pixel(x1, y1) * fx;
+    top * (1f32 - fy) + bottom * fy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layers::dataset::DatasetKind;
+
+    fn pgm(bytes: &[u8]) -> Result<GrayImage> {
+        read_pgm(Path::new("test.pgm"), bytes)
+    }
+
+    // image of the given size with a filled rectangle, dark on light when inverted
+    fn rectangle(
+        (width, height): (usize, usize),
+        (left, top, right, bottom): (usize, usize, usize, usize),
+        inverted: bool,
+    ) -> GrayImage {
+        let mut pixels = vec![if inverted { 1f32 } else { 0f32 }; width * height];
+        for y in top..bottom {
+            for x in left..right {
+                pixels[y * width + x] = if inverted { 0f32 } else { 1f32 };
+            }
+        }
+        GrayImage {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    #[test]
+    fn reads_ascii_and_binary_pgm() {
+        let image = pgm(b"P2\n# a comment\n3 2\n4\n0 1 2\n3 4 9\n").unwrap();
+        assert_eq!((image.width, image.height), (3, 2));
+        assert_eq!(image.pixels, vec![0.0, 0.25, 0.5, 0.75, 1.0, 1.0]);
+
+        let image = pgm(b"P5 2 1 255\n\x00\xff").unwrap();
+        assert_eq!(image.pixels, vec![0.0, 1.0]);
+        let image = pgm(b"P5 2 1 65535\n\x00\x00\xff\xff").unwrap();
+        assert_eq!(image.pixels, vec![0.0, 1.0]);
+    }
+
+    #[test]
+    fn rejects_malformed_pgm() {
+        assert!(pgm(b"P2 2 2 255\n1 2 3\n").is_err());
+        assert!(pgm(b"P5 2 2 255\n\x00\x00\x00").is_err());
+        assert!(pgm(b"P5 2 2 65535\n\x00\x00\x00\x00").is_err());
+        assert!(pgm(b"P2 0 2 255\n").is_err());
+        assert!(pgm(b"P2 2 x 255\n").is_err());
+        // sizes whose product overflows, or that are far larger than the file
+        assert!(pgm(b"P5 18446744073709551615 18446744073709551615 255\n\x00").is_err());
+        assert!(pgm(b"P5 4294967296 4294967296 255\n\x00").is_err());
+        assert!(pgm(b"P2 100000 100000 255\n0 0 0").is_err());
+    }
+
+    #[test]
+    fn centers_a_digit_in_the_middle_of_the_image() {
+        let column = to_column(
+            &rectangle((40, 40), (0, 0, 10, 20), false),
+            DatasetKind::Mnist.framing(),
+        );
+        let out = column.into_shape((IMAGE_SIZE, IMAGE_SIZE)).unwrap();
+        // the 10x20 rectangle keeps its size and is centered, with its edges softened by sampling
+        for ((y, x), value) in out.indexed_iter() {
+            if !((4..24).contains(&y) && (9..19).contains(&x)) {
+                assert_eq!(*value, 0.0, "at {}, {}", x, y);
+            } else if (5..23).contains(&y) && (10..18).contains(&x) {
+                assert_eq!(*value, 255.0 / 256.0, "at {}, {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn fashion_images_fill_the_frame_centered_by_their_box() {
+        // a thick bar along the top of a thin line, whose center of mass is far above the middle
+        // of its box
+        let mut image = rectangle((40, 40), (0, 0, 40, 4), false);
+        for y in 4..20 {
+            image.pixels[y * 40] = 1f32;
+        }
+        let column = to_column(&image, DatasetKind::FashionMnist.framing());
+        let out = column.into_shape((IMAGE_SIZE, IMAGE_SIZE)).unwrap();
+        // the 40x20 box is scaled to 28x14 and sits in the middle of the image
+        for ((y, x), value) in out.indexed_iter() {
+            if !(7..21).contains(&y) {
+                assert_eq!(*value, 0.0, "at {}, {}", x, y);
+            }
+        }
+        assert!(out[[7, 0]] > 0.0 && out[[7, IMAGE_SIZE - 1]] > 0.0);
+    }
+
+    #[test]
+    fn inverts_dark_digits_on_a_light_background() {
+        let light = to_column(
+            &rectangle((30, 30), (12, 5, 18, 25), false),
+            DatasetKind::Mnist.framing(),
+        );
+        let dark = to_column(
+            &rectangle((30, 30), (12, 5, 18, 25), true),
+            DatasetKind::Mnist.framing(),
+        );
+        assert_eq!(light, dark);
+        assert!(light.iter().any(|value| *value > 0.0));
+    }
+
+    #[test]
+    fn blank_images_stay_blank() {
+        for inverted in [false, true] {
+            let column = to_column(
+                &rectangle((5, 7), (0, 0, 0, 0), inverted),
+                DatasetKind::Mnist.framing(),
+            );
+            assert_eq!(column.len(), IMAGE_SIZE * IMAGE_SIZE);
+            assert!(column.iter().all(|value| *value == 0.0));
+        }
+    }
+}
@@ -1,4 +1,17 @@
+pub mod activation;
+pub mod batchnorm;
+pub mod conv2d;
 pub mod dataset;
-pub(crate) mod layer;
+pub mod dropout;
+pub mod elu;
+pub mod gelu;
+pub mod idx;
+pub mod initializer;
+pub mod layer;
+pub mod maxpool2d;
+pub mod prelu;
 pub mod relu;
+pub mod sigmoid;
+pub mod silu;
 pub mod softmax;
+pub mod tanh;
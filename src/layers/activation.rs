@@ -0,0 +1,68 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use ndarray_rand::rand::Rng;
+
+use super::{
+    elu::ELU, gelu::GELU, initializer::Initializer, layer::ActivationLayer, prelu::PReLU,
+    relu::ReLU, sigmoid::Sigmoid, silu::SiLU, tanh::Tanh,
+};
+use crate::error::Result;
+
+// activation functions available for the hidden layers, each initializing its weights the way
+// that suits it best
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Activation {
+    // leaky relu with a coefficient of 0.01
+    #[default]
+    #[clap(name = "relu")]
+    ReLU,
+    #[clap(name = "sigmoid")]
+    Sigmoid,
+    #[clap(name = "tanh")]
+    Tanh,
+    #[clap(name = "gelu")]
+    GELU,
+    #[clap(name = "silu")]
+    SiLU,
+    // elu with a coefficient of 1
+    #[clap(name = "elu")]
+    ELU,
+    // relu with a learned slope for negative inputs
+    #[clap(name = "prelu")]
+    PReLU,
+}
+
+impl Activation {
+    // initializer that suits this activation, xavier for the saturating ones and he for the
+    // ones that behave like relu
+    pub fn initializer(self) -> Initializer {
+        match self {
+            Activation::Sigmoid | Activation::Tanh => Initializer::XavierNormal,
+            Activation::ReLU
+            | Activation::GELU
+            | Activation::SiLU
+            | Activation::ELU
+            | Activation::PReLU => Initializer::HeNormal,
+        }
+    }
+
+    // create a hidden layer using this activation, with weights drawn by initializer
+    pub fn build<R: Rng + ?Sized>(
+        self,
+        input: usize,
+        nodes: usize,
+        initializer: Initializer,
+        rng: &mut R,
+    ) -> Result<Box<dyn ActivationLayer>> {
+        Ok(match self {
+            Activation::ReLU => Box::new(ReLU::new(input, nodes, 0.01, initializer, rng)?),
+            Activation::Sigmoid => Box::new(Sigmoid::new(input, nodes, initializer, rng)?),
+            Activation::Tanh => Box::new(Tanh::new(input, nodes, initializer, rng)?),
+            Activation::GELU => Box::new(GELU::new(input, nodes, initializer, rng)?),
+            Activation::SiLU => Box::new(SiLU::new(input, nodes, initializer, rng)?),
+            Activation::ELU => Box::new(ELU::new(input, nodes, 1.0, initializer, rng)?),
+            Activation::PReLU => Box::new(PReLU::new(input, nodes, initializer, rng)?),
+        })
+    }
+}
@@ -0,0 +1,239 @@
+use super::layer::{ActivationLayer, Layer};
+use crate::checkpoint::{LayerCheckpoint, LayerSpec};
+use crate::error::{Error, Result};
+use crate::model::CONFIG;
+use ndarray::{prelude::Array2, s};
+
+// added to the variance before taking its square root, so constant inputs do not divide by 0
+const EPSILON: f32 = 1e-5;
+
+// batch normalization, normalizing each channel to zero mean and unit variance before scaling it
+// by a learned gamma and shifting it by a learned beta
+// while training the statistics come from the batch, and running averages of them are kept for
+// evaluating, where the batch can be any size
+// gamma is stored in the weights and beta in the biases, one row per channel
+pub struct BatchNorm {
+    pub layer: Layer,
+    channels: usize,
+    // amount of rows of each channel, height * width after a convolution and 1 after a dense layer
+    size: usize,
+    // weight of each new batch in the running statistics
+    momentum: f32,
+    // whether the batch statistics are used, set by set_mode
+    training: bool,
+    // channels x 1 running averages of the mean and variance of each channel
+    pub running_mean: Array2<f32>,
+    pub running_var: Array2<f32>,
+    // channels x 1 inverse standard deviation each channel was normalized with in the last
+    // forward prop
+    inv_std: Array2<f32>,
+}
+
+impl BatchNorm {
+    // create new batch normalization layer, starting in training mode with gamma 1 and beta 0
+    // channels: amount of channels, or nodes after a dense layer
+    // size: rows of each channel, height * width of the feature maps after a convolution
+    // momentum: weight of each new batch in the running statistics
+    pub fn new(channels: usize, size: usize, momentum: f32) -> Result<BatchNorm> {
+        if channels == 0 || size == 0 {
+            return Err(Error::InvalidConfig(format!(
+                "a BatchNorm layer needs at least one channel and one row per channel, got {} \
+                 channels of {} rows",
+                channels, size
+            )));
+        }
+        if !(momentum > 0.0 && momentum <= 1.0) {
+            return Err(Error::InvalidConfig(format!(
+                "batch norm momentum must be above 0 and at most 1, got {}",
+                momentum
+            )));
+        }
+        // the layer holds gamma and beta per channel, its output has every input row
+        let mut layer = Layer::new_layer(1, channels);
+        layer.weights = Array2::<f32>::ones((channels, 1));
+        layer.preactivation = Array2::<f32>::zeros((channels * size, 0));
+        layer.layer = Array2::<f32>::zeros((channels * size, 0));
+        layer.d_activation = Array2::<f32>::zeros((channels * size, 0));
+        Ok(BatchNorm {
+            layer,
+            channels,
+            size,
+            momentum,
+            training: true,
+            running_mean: Array2::<f32>::zeros((channels, 1)),
+            running_var: Array2::<f32>::ones((channels, 1)),
+            inv_std: Array2::<f32>::ones((channels, 1)),
+        })
+    }
+
+    // repeat a value per channel for every row of that channel
+    fn per_row(&self, values: &Array2<f32>) -> Array2<f32> {
+        Array2::from_shape_fn((self.channels * self.size, 1), |(i, _)| {
+            values[[i / self.size, 0]]
+        })
+    }
+}
+
+impl ActivationLayer for BatchNorm {
+    // scale and shift the normalized input
+    fn activate(&mut self) {
+        self.layer.layer = &self.layer.preactivation * &self.per_row(&self.layer.weights)
+            + &self.per_row(&self.layer.biases);
+    }
+
+    // gradient with respect to the output, the rest is calculated from it
+    fn deactivate(&mut self, previous_layer: &Layer) {
+        self.layer.d_activation = previous_layer.layer.clone();
+    }
+
+    // normalize with the batch statistics while training, updating the running ones, and with
+    // the running statistics otherwise
+    fn forward_prop(&mut self, previous_layer: &Layer) {
+        let input = &previous_layer.layer;
+        let mut normalized = Array2::<f32>::zeros(input.raw_dim());
+        for c in 0..self.channels {
+            let rows = s![c * self.size..(c + 1) * self.size, ..];
+            let values = input.slice(rows);
+            let count = values.len() as f32;
+            let (mean, variance) = if self.training && count > 0.0 {
+                let mean = values.sum() / count;
+                let variance = values.map(|x| (x - mean).powi(2)).sum() / count;
+                // the running variance is unbiased, as the batch is only a sample of the data
+                let unbiased = variance * count / (count - 1f32).max(1f32);
+                self.running_mean[[c, 0]] += self.momentum * (mean - self.running_mean[[c, 0]]);
+                self.running_var[[c, 0]] += self.momentum * (unbiased - self.running_var[[c, 0]]);
+                (mean, variance)
+            } else {
+                (self.running_mean[[c, 0]], self.running_var[[c, 0]])
+            };
+            let inv_std = 1f32 / (variance + EPSILON).sqrt();
+            self.inv_std[[c, 0]] = inv_std;
+            normalized
+                .slice_mut(rows)
+                .assign(&values.map(|x| (x - mean) * inv_std));
+        }
+        self.layer.preactivation = normalized;
+        self.activate();
+    }
+
+    // gradients of gamma and beta, averaged over the samples in the batch
+    fn backward_prop(&mut self, previous_layer: &Layer, _next_layer: &Layer) {
+        self.deactivate(previous_layer);
+        let samples = self.layer.d_activation.ncols() as f32;
+        let d_gamma = &self.layer.d_activation * &self.layer.preactivation;
+        for c in 0..self.channels {
+            let rows = s![c * self.size..(c + 1) * self.size, ..];
+            self.layer.d_weights[[c, 0]] = d_gamma.slice(rows).sum() / samples;
+            self.layer.d_biases[[c, 0]] = self.layer.d_activation.slice(rows).sum() / samples;
+        }
+    }
+
+    // while training every input of a channel affects its mean and variance, so the gradient of
+    // each input depends on the whole channel
+    fn input_gradient(&self) -> Array2<f32> {
+        let mut gradient = Array2::<f32>::zeros(self.layer.d_activation.raw_dim());
+        for c in 0..self.channels {
+            let rows = s![c * self.size..(c + 1) * self.size, ..];
+            let inv_std = self.inv_std[[c, 0]];
+            let d_normalized = self
+                .layer
+                .d_activation
+                .slice(rows)
+                .map(|x| x * self.layer.weights[[c, 0]]);
+            if self.training {
+                let normalized = self.layer.preactivation.slice(rows);
+                let count = d_normalized.len() as f32;
+                let sum = d_normalized.sum();
+                let dot = (&d_normalized * &normalized).sum();
+                gradient.slice_mut(rows).assign(
+                    &((&d_normalized * count - sum - &normalized * dot) * (inv_std / count)),
+                );
+            } else {
+                gradient.slice_mut(rows).assign(&(d_normalized * inv_std));
+            }
+        }
+        gradient
+    }
+
+    // only use the batch statistics while training
+    fn set_mode(&mut self, mode: CONFIG) {
+        self.training = matches!(mode, CONFIG::TRAIN);
+    }
+
+    fn layer(&self) -> &Layer {
+        &self.layer
+    }
+
+    fn layer_mut(&mut self) -> &mut Layer {
+        &mut self.layer
+    }
+
+    fn spec(&self) -> LayerSpec {
+        LayerSpec::BatchNorm {
+            channels: self.channels,
+            size: self.size,
+            momentum: self.momentum,
+        }
+    }
+
+    // the running statistics are saved along with gamma and beta
+    fn checkpoint(&self) -> LayerCheckpoint {
+        LayerCheckpoint {
+            spec: self.spec(),
+            weights: self.layer.weights.clone(),
+            biases: self.layer.biases.clone(),
+            buffers: vec![self.running_mean.clone(), self.running_var.clone()],
+        }
+    }
+
+    fn load_checkpoint(&mut self, checkpoint: &LayerCheckpoint) {
+        self.layer.weights = checkpoint.weights.clone();
+        self.layer.biases = checkpoint.biases.clone();
+        if let [mean, var] = checkpoint.buffers.as_slice() {
+            self.running_mean = mean.clone();
+            self.running_var = var.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gradient_check::{check_layer, random};
+
+    #[test]
+    fn batchnorm_gradients() {
+        let mut norm = BatchNorm::new(3, 1, 0.1).unwrap();
+        norm.layer.weights = random((3, 1), 1);
+        norm.layer.biases = random((3, 1), 2);
+        check_layer(&mut norm, &random((3, 5), 3), 4);
+    }
+
+    #[test]
+    fn batchnorm_conv_gradients() {
+        let mut norm = BatchNorm::new(2, 4, 0.1).unwrap();
+        norm.layer.weights = random((2, 1), 5);
+        norm.layer.biases = random((2, 1), 6);
+        check_layer(&mut norm, &random((2 * 4, 3), 7), 8);
+    }
+
+    #[test]
+    fn batchnorm_evaluates_with_running_statistics() {
+        let mut norm = BatchNorm::new(2, 3, 1.0).unwrap();
+        let input = random((2 * 3, 4), 9).map(|x| x * 3.0 + 2.0);
+        norm.forward_prop(&Layer::dummy_layer(input.clone()));
+        let trained = norm.layer.layer.clone();
+        // with a momentum of 1 the running statistics are exactly those of the last batch, up to
+        // the correction for the variance being unbiased
+        norm.set_mode(CONFIG::TEST);
+        for c in 0..2 {
+            let count = 12f32;
+            norm.running_var[[c, 0]] *= (count - 1f32) / count;
+        }
+        norm.forward_prop(&Layer::dummy_layer(input.clone()));
+        for (a, b) in norm.layer.layer.iter().zip(trained.iter()) {
+            assert!((a - b).abs() < 1e-4);
+        }
+        check_layer(&mut norm, &input, 10);
+    }
+}
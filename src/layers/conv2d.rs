@@ -0,0 +1,277 @@
+use super::initializer::Initializer;
+use super::layer::{ActivationLayer, Layer};
+use crate::checkpoint::LayerSpec;
+use crate::error::{Error, Result};
+use ndarray::{prelude::Array2, Array1, ArrayView1, Axis};
+use ndarray_rand::rand::Rng;
+
+// 2d convolution with stride 1 followed by (leaky) relu
+// each column of the input holds one sample's channels one after another, each in row major order
+// each column of the output holds one sample's feature maps in the same order
+pub struct Conv2d {
+    // weights are filters x (channels * kernel * kernel), biases are one per filter
+    pub layer: Layer,
+    channels: usize,
+    height: usize,
+    width: usize,
+    filters: usize,
+    kernel: usize,
+    padding: usize,
+    relu_coefficient: f32,
+}
+
+impl Conv2d {
+    // create new convolutional layer
+    // channels, height, width: shape of each input sample
+    // filters: amount of output channels
+    // kernel: width and height of each filter
+    // padding: zeros added around each side of the input
+    // initializer: how the filters are drawn, he normal suits the relu
+    #[allow(clippy::too_many_arguments)]
+    pub fn new<R: Rng + ?Sized>(
+        channels: usize,
+        height: usize,
+        width: usize,
+        filters: usize,
+        kernel: usize,
+        padding: usize,
+        relu_coefficient: f32,
+        initializer: Initializer,
+        rng: &mut R,
+    ) -> Result<Conv2d> {
+        if channels == 0 || height == 0 || width == 0 || filters == 0 || kernel == 0 {
+            return Err(Error::InvalidConfig(format!(
+                "a Conv2d layer needs nonzero sizes, got {} channels of {}x{}, {} filters and a {}x{} kernel",
+                channels, height, width, filters, kernel, kernel
+            )));
+        }
+        if kernel > height + 2 * padding || kernel > width + 2 * padding {
+            return Err(Error::InvalidConfig(format!(
+                "a {}x{} kernel does not fit {}x{} inputs with {} padding",
+                kernel, kernel, height, width, padding
+            )));
+        }
+        let fan_in = channels * kernel * kernel;
+        let out_height = height + 2 * padding + 1 - kernel;
+        let out_width = width + 2 * padding + 1 - kernel;
+        // nodes are every position of every feature map
+        let mut layer = Layer::new_layer(fan_in, filters * out_height * out_width);
+        // draw the filters, each input position feeds every filter at kernel * kernel offsets
+        layer.weights =
+            initializer.initialize((filters, fan_in), fan_in, filters * kernel * kernel, rng)?;
+        layer.initializer = initializer;
+        layer.d_weights = Array2::<f32>::zeros((filters, fan_in));
+        layer.biases = Array2::<f32>::zeros((filters, 1));
+        layer.d_biases = Array2::<f32>::zeros((filters, 1));
+        Ok(Conv2d {
+            layer,
+            channels,
+            height,
+            width,
+            filters,
+            kernel,
+            padding,
+            relu_coefficient,
+        })
+    }
+
+    // height of each output feature map
+    pub fn out_height(&self) -> usize {
+        self.height + 2 * self.padding + 1 - self.kernel
+    }
+
+    // width of each output feature map
+    pub fn out_width(&self) -> usize {
+        self.width + 2 * self.padding + 1 - self.kernel
+    }
+
+    // input pixel covered by a kernel position, or None if it lands in the padding
+    fn input_index(&self, c: usize, y: usize, x: usize, ky: usize, kx: usize) -> Option<usize> {
+        let iy = (y + ky).checked_sub(self.padding)?;
+        let ix = (x + kx).checked_sub(self.padding)?;
+        if iy < self.height && ix < self.width {
+            Some((c * self.height + iy) * self.width + ix)
+        } else {
+            None
+        }
+    }
+
+    // unroll every patch of a sample into a column, so the convolution becomes a single dot
+    // output is (channels * kernel * kernel) x (out_height * out_width)
+    fn im2col(&self, sample: ArrayView1<f32>) -> Array2<f32> {
+        let (out_height, out_width) = (self.out_height(), self.out_width());
+        let mut columns = Array2::<f32>::zeros((
+            self.channels * self.kernel * self.kernel,
+            out_height * out_width,
+        ));
+        for c in 0..self.channels {
+            for ky in 0..self.kernel {
+                for kx in 0..self.kernel {
+                    let row = (c * self.kernel + ky) * self.kernel + kx;
+                    for y in 0..out_height {
+                        for x in 0..out_width {
+                            if let Some(index) = self.input_index(c, y, x, ky, kx) {
+                                columns[[row, y * out_width + x]] = sample[index];
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        columns
+    }
+
+    // inverse of im2col, summing the gradient of every patch back onto the input pixels
+    fn col2im(&self, columns: &Array2<f32>) -> Array1<f32> {
+        let (out_height, out_width) = (self.out_height(), self.out_width());
+        let mut sample = Array1::<f32>::zeros(self.channels * self.height * self.width);
+        for c in 0..self.channels {
+            for ky in 0..self.kernel {
+                for kx in 0..self.kernel {
+                    let row = (c * self.kernel + ky) * self.kernel + kx;
+                    for y in 0..out_height {
+                        for x in 0..out_width {
+                            if let Some(index) = self.input_index(c, y, x, ky, kx) {
+                                sample[index] += columns[[row, y * out_width + x]];
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        sample
+    }
+
+    // view one column of a layer as filters x positions
+    fn feature_maps(&self, column: ArrayView1<f32>) -> Array2<f32> {
+        column
+            .to_owned()
+            .into_shape((self.filters, self.out_height() * self.out_width()))
+            .unwrap()
+    }
+
+    // calculate derivative of relu
+    fn derivate(&self) -> Array2<f32> {
+        self.layer
+            .layer
+            .map(|x| if *x > 0.0 { 1.0 } else { self.relu_coefficient })
+    }
+}
+
+impl ActivationLayer for Conv2d {
+    // activate using relu piecewise function
+    fn activate(&mut self) {
+        let relu_coefficient = self.relu_coefficient;
+        self.layer.layer =
+            self.layer
+                .preactivation
+                .map(|x| if *x < 0.0 { x * relu_coefficient } else { *x });
+    }
+
+    // calculate gradient of the layer
+    fn deactivate(&mut self, previous_layer: &Layer) {
+        self.layer.d_activation = &previous_layer.layer * &self.derivate();
+    }
+
+    // convolve each sample with every filter
+    fn forward_prop(&mut self, previous_layer: &Layer) {
+        let samples = previous_layer.layer.ncols();
+        let mut preactivation = Array2::<f32>::zeros((self.layer.layer.nrows(), samples));
+        for (j, sample) in previous_layer.layer.axis_iter(Axis(1)).enumerate() {
+            let maps = &self.layer.weights.dot(&self.im2col(sample)) + &self.layer.biases;
+            preactivation
+                .column_mut(j)
+                .assign(&Array1::from_iter(maps.iter().cloned()));
+        }
+        self.layer.preactivation = preactivation;
+        self.activate();
+    }
+
+    // accumulate the gradient of each filter over every sample and position
+    fn backward_prop(&mut self, previous_layer: &Layer, next_layer: &Layer) {
+        self.deactivate(previous_layer);
+        let samples = next_layer.layer.ncols() as f32;
+        let mut d_weights = Array2::<f32>::zeros(self.layer.weights.raw_dim());
+        let mut d_biases = Array2::<f32>::zeros(self.layer.biases.raw_dim());
+        for (sample, d_column) in next_layer
+            .layer
+            .axis_iter(Axis(1))
+            .zip(self.layer.d_activation.axis_iter(Axis(1)))
+        {
+            let d_maps = self.feature_maps(d_column);
+            d_weights += &d_maps.dot(&self.im2col(sample).t());
+            d_biases += &d_maps.sum_axis(Axis(1)).insert_axis(Axis(1));
+        }
+        self.layer.d_weights = d_weights.map(|x| x / samples);
+        self.layer.d_biases = d_biases.map(|x| x / samples);
+    }
+
+    // scatter the gradient of every patch back onto the input of each sample
+    fn input_gradient(&self) -> Array2<f32> {
+        let mut gradient = Array2::<f32>::zeros((
+            self.channels * self.height * self.width,
+            self.layer.d_activation.ncols(),
+        ));
+        for (j, d_column) in self.layer.d_activation.axis_iter(Axis(1)).enumerate() {
+            let d_columns = self.layer.weights.t().dot(&self.feature_maps(d_column));
+            gradient.column_mut(j).assign(&self.col2im(&d_columns));
+        }
+        gradient
+    }
+
+    fn layer(&self) -> &Layer {
+        &self.layer
+    }
+
+    fn layer_mut(&mut self) -> &mut Layer {
+        &mut self.layer
+    }
+
+    fn spec(&self) -> LayerSpec {
+        LayerSpec::Conv2d {
+            channels: self.channels,
+            height: self.height,
+            width: self.width,
+            filters: self.filters,
+            kernel: self.kernel,
+            padding: self.padding,
+            relu_coefficient: self.relu_coefficient,
+            initializer: self.layer.initializer,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gradient_check::{check_layer, random};
+    use ndarray_rand::rand::{rngs::StdRng, SeedableRng};
+
+    fn check_conv(padding: usize, relu_coefficient: f32, seed: u64) {
+        let mut conv = Conv2d::new(
+            2,
+            5,
+            4,
+            3,
+            3,
+            padding,
+            relu_coefficient,
+            Initializer::HeNormal,
+            &mut StdRng::seed_from_u64(0),
+        )
+        .unwrap();
+        conv.layer.weights = random((3, 2 * 3 * 3), seed);
+        conv.layer.biases = random((3, 1), seed + 1);
+        check_layer(&mut conv, &random((2 * 5 * 4, 2), seed + 2), seed + 3);
+    }
+
+    #[test]
+    fn conv2d_gradients() {
+        check_conv(0, 0.0, 1);
+    }
+
+    #[test]
+    fn padded_leaky_conv2d_gradients() {
+        check_conv(1, 0.1, 5);
+    }
+}
@@ -1,188 +1,497 @@
-use mnist::*;
-use ndarray::s;
-use ndarray::{Array2, Axis, Slice};
+use std::path::Path;
+
+use clap::ValueEnum;
+use ndarray::{Array2, Axis};
+use rand::seq::SliceRandom;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
+use super::idx;
 use super::layer::Layer;
+use crate::error::{Error, Result};
+use crate::image::Framing;
 use crate::model::CONFIG;
 
-// MNIST dataset converted to layers for interfacing with the model
+// names of the classes of fashion mnist, in order of their labels
+const FASHION_MNIST_LABELS: [&str; 10] = [
+    "T-shirt/top",
+    "Trouser",
+    "Pullover",
+    "Dress",
+    "Coat",
+    "Sandal",
+    "Shirt",
+    "Sneaker",
+    "Bag",
+    "Ankle boot",
+];
+// hiragana of the rows of the kana table kmnist is split into, in order of their labels
+const KMNIST_LABELS: [&str; 10] = ["お", "き", "す", "つ", "な", "は", "ま", "や", "れ", "を"];
+// digits, then upper case letters, then the lower case letters that are not merged with their
+// upper case, in order of the emnist balanced labels
+const EMNIST_BALANCED_LABELS: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabdefghnqrt";
+
+// datasets distributed in the same idx format as mnist
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum DatasetKind {
+    // handwritten digits
+    #[default]
+    Mnist,
+    // pictures of clothing, shoes and bags
+    #[clap(name = "fashion-mnist")]
+    FashionMnist,
+    // handwritten hiragana, one character for each row of the kana table
+    Kmnist,
+    // handwritten digits and letters, with upper and lower case merged for letters that look alike
+    #[clap(name = "emnist-balanced")]
+    EmnistBalanced,
+    // handwritten letters, with upper and lower case merged
+    #[clap(name = "emnist-letters")]
+    EmnistLetters,
+}
+
+impl DatasetKind {
+    // amount of classes the labels are split into
+    pub fn classes(&self) -> usize {
+        match self {
+            DatasetKind::Mnist | DatasetKind::FashionMnist | DatasetKind::Kmnist => 10,
+            DatasetKind::EmnistBalanced => 47,
+            DatasetKind::EmnistLetters => 26,
+        }
+    }
+
+    // name of a class, for printing predictions
+    pub fn label(&self, class: usize) -> String {
+        match self {
+            DatasetKind::Mnist => class.to_string(),
+            DatasetKind::FashionMnist => FASHION_MNIST_LABELS[class].to_string(),
+            DatasetKind::Kmnist => KMNIST_LABELS[class].to_string(),
+            DatasetKind::EmnistBalanced => EMNIST_BALANCED_LABELS[class..class + 1].to_string(),
+            DatasetKind::EmnistLetters => char::from(b'a' + class as u8).to_string(),
+        }
+    }
+
+    // how the images were fit into their 28x28 frame, so images to predict can be processed the
+    // same way
+    // mnist digits were scaled into a 20x20 box and centered by their center of mass, emnist
+    // characters into the middle of a 24x24 box, while fashion mnist clothing and kmnist
+    // characters fill the frame
+    pub fn framing(&self) -> Framing {
+        match self {
+            DatasetKind::Mnist => Framing {
+                size: 20,
+                center_of_mass: true,
+            },
+            DatasetKind::EmnistBalanced | DatasetKind::EmnistLetters => Framing {
+                size: 24,
+                center_of_mass: false,
+            },
+            DatasetKind::FashionMnist | DatasetKind::Kmnist => Framing {
+                size: 28,
+                center_of_mass: false,
+            },
+        }
+    }
+
+    // start of the training and testing file names, before -images-idx3-ubyte or
+    // -labels-idx1-ubyte
+    fn prefixes(&self) -> (&'static str, &'static str) {
+        match self {
+            DatasetKind::Mnist | DatasetKind::FashionMnist | DatasetKind::Kmnist => {
+                ("train", "t10k")
+            }
+            DatasetKind::EmnistBalanced => ("emnist-balanced-train", "emnist-balanced-test"),
+            DatasetKind::EmnistLetters => ("emnist-letters-train", "emnist-letters-test"),
+        }
+    }
+
+    // emnist images are stored column major, so they have to be transposed to be upright like
+    // the images of the other datasets
+    fn transposed(&self) -> bool {
+        matches!(
+            self,
+            DatasetKind::EmnistBalanced | DatasetKind::EmnistLetters
+        )
+    }
+
+    // label of the first class in the files, emnist letters counts from 1
+    fn first_label(&self) -> u8 {
+        match self {
+            DatasetKind::EmnistLetters => 1,
+            _ => 0,
+        }
+    }
+}
+
+// idx dataset converted to layers for interfacing with the model
 pub struct Dataset {
-    // Full dataset
+    // Full dataset, minus the samples held out for validation
     pub training_data: Layer,
     pub training_labels: Layer,
 
+    // samples held out from the end of the training set for validation, may be empty
+    pub validation_data: Layer,
+    pub validation_labels: Layer,
+
     // Full testing set
     pub testing_data: Layer,
     pub testing_labels: Layer,
 
-    // Slice of training set
-    pub train_data_slice: Layer,
-    pub train_label_slice: Layer,
-
-    // Slice of testing set
-    pub test_data_slice: Layer,
-    pub test_label_slice: Layer,
+    // dimensions of each image
+    pub rows: usize,
+    pub cols: usize,
 
-    // size of the slices, stored for access in other structs
-    pub slice_range: isize,
+    // which dataset was loaded, and the amount of classes its labels are split into
+    pub kind: DatasetKind,
+    pub classes: usize,
 }
 
 impl Dataset {
-    // function shuffles the training data and labels in the same order
-    pub fn shuffle(&mut self) {
-        let mut rng = rand::thread_rng();
-        let shared_length = self
-            .training_data
-            .layer
-            .index_axis_mut(ndarray::Axis(0), 0)
-            .len();
-        assert_eq!(
-            self.training_data.layer.ncols(),
-            self.training_labels.layer.ncols()
-        );
-        for i in 0..shared_length {
-            let next = rng.gen_range(0..shared_length);
-            if i != next {
-                let (mut index, mut random) = self
-                    .training_data
-                    .layer
-                    .multi_slice_mut((s![.., i], s![.., next]));
-                std::mem::swap(&mut index, &mut random);
-
-                let (mut index_2, mut random_2) = self
-                    .training_labels
-                    .layer
-                    .multi_slice_mut((s![.., i], s![.., next]));
-                std::mem::swap(&mut index_2, &mut random_2);
+    // function converts vectors read from idx files to Array2<f32>
+    pub fn vec_to_array(vector: &[u8], n: usize, m: usize, data: bool) -> Result<Array2<f32>> {
+        let array = Array2::from_shape_vec((n, m), vector.to_vec()).map_err(|_| {
+            Error::ShapeMismatch(format!(
+                "cannot arrange {} values into {} samples of {}",
+                vector.len(),
+                n,
+                m
+            ))
+        })?;
+        if data {
+            Ok(array.t().map(|x| *x as f32).map(|x| *x / 256f32))
+        } else {
+            Ok(array.t().map(|x| *x as f32))
+        }
+    }
+
+    // read the images and labels of one set, making sure they describe the same samples
+    // images are turned upright and labels shifted to start from 0 as needed for the kind
+    fn read_set(
+        data_dir: &Path,
+        kind: DatasetKind,
+        prefix: &str,
+    ) -> Result<(idx::IdxImages, Vec<u8>)> {
+        let mut images = idx::read_images(&idx::find_file(
+            data_dir,
+            &format!("{}-images-idx3-ubyte", prefix),
+        )?)?;
+        let labels_path = idx::find_file(data_dir, &format!("{}-labels-idx1-ubyte", prefix))?;
+        let labels = idx::read_labels(&labels_path)?;
+        if images.count != labels.len() {
+            return Err(Error::invalid_file(
+                &labels_path,
+                format!(
+                    "{} set has {} images but {} labels",
+                    prefix,
+                    images.count,
+                    labels.len()
+                ),
+            ));
+        }
+        let first = kind.first_label();
+        let classes = kind.classes();
+        if let Some(label) = labels
+            .iter()
+            .find(|label| **label < first || (**label - first) as usize >= classes)
+        {
+            return Err(Error::invalid_file(
+                &labels_path,
+                format!(
+                    "label {} is not one of the {} classes of {:?}, check --dataset",
+                    label, classes, kind
+                ),
+            ));
+        }
+        let labels = labels.iter().map(|label| label - first).collect();
+        if kind.transposed() {
+            let (rows, cols) = (images.rows, images.cols);
+            let mut pixels = vec![0u8; images.pixels.len()];
+            for (image, transposed) in images
+                .pixels
+                .chunks(rows * cols)
+                .zip(pixels.chunks_mut(rows * cols))
+            {
+                for y in 0..rows {
+                    for x in 0..cols {
+                        transposed[x * rows + y] = image[y * cols + x];
+                    }
+                }
             }
+            images.pixels = pixels;
+            (images.rows, images.cols) = (cols, rows);
         }
+        Ok((images, labels))
     }
 
-    // function converts vectors gotten from MNISTBuilder to Array2<f32>
-    pub fn vec_to_array(vector: &Vec<u8>, n: usize, m: usize, data: bool) -> Array2<f32> {
-        if data {
-            Array2::from_shape_vec((n, m), vector.to_vec())
-                .expect("Error converting images to Array3 struct")
-                .t()
-                .map(|x| *x as f32)
-                .map(|x| *x / 256f32)
-        } else {
-            Array2::from_shape_vec((n, m), vector.to_vec())
-                .expect("Error converting images to Array3 struct")
-                .t()
-                .map(|x| *x as f32)
-        }
-    }
-
-    // create a new dataset
-    pub fn new(slice_range: isize) -> Dataset {
-        let Mnist {
-            trn_img,
-            trn_lbl,
-            tst_img,
-            tst_lbl,
-            ..
-        }: Mnist = MnistBuilder::new()
-            .label_format_digit()
-            .training_set_length(50_000)
-            .validation_set_length(0)
-            .test_set_length(20_000)
-            .finalize();
-
-        // create each array, and assert that it is not empyt data
-        let training_data: Array2<f32> = Dataset::vec_to_array(&trn_img, 50_000, 784, true);
-        assert!(!training_data.is_empty());
-        let training_labels: Array2<f32> = Dataset::vec_to_array(&trn_lbl, 50_000, 1, false);
-        assert!(!training_labels.is_empty());
-        let testing_data: Array2<f32> = Dataset::vec_to_array(&tst_img, 20_000, 784, true);
-        assert!(!testing_data.is_empty());
-        let testing_labels: Array2<f32> = Dataset::vec_to_array(&tst_lbl, 20_000, 1, false);
-        assert!(!testing_labels.is_empty());
-
-        // slice of training set
-        let train_data_slice = Array2::<f32>::zeros((slice_range as usize, 784));
-        let train_label_slice = Array2::<f32>::zeros((slice_range as usize, 1));
-        // slice of testing set
-        let test_data_slice = Array2::<f32>::zeros((slice_range as usize, 784));
-        let test_label_slice = Array2::<f32>::zeros((slice_range as usize, 1));
-        Dataset {
+    // create a new dataset from the idx files of one kind of dataset in data_dir
+    // validation_split: fraction of the training set held out for validation, taken from its end
+    // so the same samples are held out every run
+    pub fn new(data_dir: &Path, kind: DatasetKind, validation_split: f32) -> Result<Dataset> {
+        if !(0f32..1f32).contains(&validation_split) {
+            return Err(Error::InvalidConfig(format!(
+                "validation split has to be at least 0 and below 1, got {}",
+                validation_split
+            )));
+        }
+        let (train_prefix, test_prefix) = kind.prefixes();
+        let (trn_img, trn_lbl) = Dataset::read_set(data_dir, kind, train_prefix)?;
+        let (tst_img, tst_lbl) = Dataset::read_set(data_dir, kind, test_prefix)?;
+        if (trn_img.rows, trn_img.cols) != (tst_img.rows, tst_img.cols) {
+            return Err(Error::ShapeMismatch(format!(
+                "training images are {}x{} but testing images are {}x{}",
+                trn_img.rows, trn_img.cols, tst_img.rows, tst_img.cols
+            )));
+        }
+        let (rows, cols) = (trn_img.rows, trn_img.cols);
+        let pixels = rows * cols;
+
+        // create each array, using the sizes stored in the files
+        let training_data: Array2<f32> =
+            Dataset::vec_to_array(&trn_img.pixels, trn_img.count, pixels, true)?;
+        let training_labels: Array2<f32> =
+            Dataset::vec_to_array(&trn_lbl, trn_lbl.len(), 1, false)?;
+        // split off the validation samples, always leaving at least one training sample
+        let held_out =
+            ((trn_img.count as f32 * validation_split).round() as usize).min(trn_img.count - 1);
+        let split = trn_img.count - held_out;
+        // select copies into a fresh array, unlike views split off at the very end which are empty
+        // with strides that cannot be copied
+        let training: Vec<usize> = (0..split).collect();
+        let validation: Vec<usize> = (split..trn_img.count).collect();
+        let validation_data = training_data.select(Axis(1), &validation);
+        let validation_labels = training_labels.select(Axis(1), &validation);
+        let training_data = training_data.select(Axis(1), &training);
+        let training_labels = training_labels.select(Axis(1), &training);
+        let testing_data: Array2<f32> =
+            Dataset::vec_to_array(&tst_img.pixels, tst_img.count, pixels, true)?;
+        let testing_labels: Array2<f32> = Dataset::vec_to_array(&tst_lbl, tst_lbl.len(), 1, false)?;
+
+        Ok(Dataset {
             training_data: Layer::dummy_layer(training_data),
             training_labels: Layer::dummy_layer(training_labels),
+            validation_data: Layer::dummy_layer(validation_data),
+            validation_labels: Layer::dummy_layer(validation_labels),
             testing_data: Layer::dummy_layer(testing_data),
             testing_labels: Layer::dummy_layer(testing_labels),
-            train_data_slice: Layer::dummy_layer(train_data_slice),
-            train_label_slice: Layer::dummy_layer(train_label_slice),
-            test_data_slice: Layer::dummy_layer(test_data_slice),
-            test_label_slice: Layer::dummy_layer(test_label_slice),
-            slice_range,
+            rows,
+            cols,
+            kind,
+            classes: kind.classes(),
+        })
+    }
+
+    // iterator over the mini-batches of one epoch, visiting every sample exactly once
+    // batch_size: samples in each batch, except for a smaller last one
+    // TRAIN: batches of the training set in a new random order each call, drawn from rng
+    // VALIDATE: batches of the validation set in file order
+    // TEST: batches of the testing set in file order
+    pub fn batches<R: Rng + ?Sized>(
+        &self,
+        mode: CONFIG,
+        batch_size: usize,
+        rng: &mut R,
+    ) -> Batches {
+        let mut order: Vec<usize> = (0..self.samples(mode)).collect();
+        if let CONFIG::TRAIN = mode {
+            order.shuffle(rng);
+        }
+        Batches {
+            order,
+            position: 0,
+            batch_size,
         }
     }
 
-    // sets slice variables randomly
-    pub fn set_slice(&mut self, mode: CONFIG) {
-        let mut rng = rand::thread_rng();
+    // gathers the samples at the given indices into a data and a label layer
+    pub fn batch(&self, mode: CONFIG, indices: &[usize]) -> (Layer, Layer) {
+        let (data, labels) = self.set(mode);
+        (
+            Layer::dummy_layer(data.layer.select(Axis(1), indices)),
+            Layer::dummy_layer(labels.layer.select(Axis(1), indices)),
+        )
+    }
+
+    // amount of samples in a set
+    pub fn samples(&self, mode: CONFIG) -> usize {
+        self.set(mode).0.layer.ncols()
+    }
+
+    // data and labels of a set
+    fn set(&self, mode: CONFIG) -> (&Layer, &Layer) {
         match mode {
-            CONFIG::TRAIN => {
-                // generate start and end value
-                let mut start = rng.gen_range(0..self.training_data.layer.ncols()) as isize;
-                let mut end = &start + &self.slice_range;
-                // make sure that index of end exists
-                if (start + self.slice_range)
-                    >= (self.training_data.layer.ncols() - 1usize) as isize
-                {
-                    end = &start - &self.slice_range;
-                }
-                // swaps start and end if end is larger
-                if start > end {
-                    let temp = start;
-                    start = end;
-                    end = temp;
-                }
-                // slices data and converts to a separate array in memory
-                self.train_data_slice = Layer::dummy_layer(
-                    self.training_data
-                        .layer
-                        .slice_axis(Axis(1), Slice::new(start, Some(end), 1))
-                        .clone()
-                        .to_owned(),
-                );
-                self.train_label_slice = Layer::dummy_layer(
-                    self.training_labels
-                        .layer
-                        .slice_axis(Axis(1), Slice::new(start, Some(end), 1))
-                        .clone()
-                        .to_owned(),
-                );
+            CONFIG::TRAIN => (&self.training_data, &self.training_labels),
+            CONFIG::VALIDATE => (&self.validation_data, &self.validation_labels),
+            CONFIG::TEST => (&self.testing_data, &self.testing_labels),
+        }
+    }
+}
+
+// indices of the samples in each mini-batch of an epoch, the last batch holds whatever is left
+// over when the set does not divide evenly into batches
+// saved in checkpoints, so an interrupted epoch continues with the batches it had left
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Batches {
+    order: Vec<usize>,
+    position: usize,
+    batch_size: usize,
+}
+
+impl Batches {
+    // amount of samples the batches of the epoch are drawn from
+    pub fn samples(&self) -> usize {
+        self.order.len()
+    }
+
+    // whether every index is one of the samples and the batches can be iterated, for batches
+    // read back from a checkpoint
+    pub fn is_valid(&self) -> bool {
+        self.batch_size > 0
+            && self.position <= self.order.len()
+            && self.order.iter().all(|&index| index < self.order.len())
+    }
+
+    // fraction of the batches of the epoch handed out so far
+    pub fn progress(&self) -> f32 {
+        self.position.div_ceil(self.batch_size) as f32
+            / self.order.len().div_ceil(self.batch_size) as f32
+    }
+}
+
+impl Iterator for Batches {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Vec<usize>> {
+        if self.position >= self.order.len() {
+            return None;
+        }
+        let end = (self.position + self.batch_size).min(self.order.len());
+        let indices = self.order[self.position..end].to_vec();
+        self.position = end;
+        Some(indices)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.order.len() - self.position).div_ceil(self.batch_size);
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for Batches {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    // write the image and label files of one set of 2x3 images to a fresh directory
+    fn write_set(name: &str, prefix: &str, images: &[[u8; 6]], labels: &[u8]) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("rustnist-set-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut bytes: Vec<u8> = [0x803u32, images.len() as u32, 2, 3]
+            .iter()
+            .flat_map(|value| value.to_be_bytes())
+            .collect();
+        bytes.extend(images.iter().flatten());
+        fs::write(dir.join(format!("{}-images-idx3-ubyte", prefix)), bytes).unwrap();
+        let mut bytes: Vec<u8> = [0x801u32, labels.len() as u32]
+            .iter()
+            .flat_map(|value| value.to_be_bytes())
+            .collect();
+        bytes.extend(labels);
+        fs::write(dir.join(format!("{}-labels-idx1-ubyte", prefix)), bytes).unwrap();
+        dir
+    }
+
+    #[test]
+    fn rejects_a_different_amount_of_images_and_labels() {
+        let dir = write_set("count", "train", &[[0; 6], [1; 6]], &[3, 4, 5]);
+        let result = Dataset::read_set(&dir, DatasetKind::Mnist, "train");
+        fs::remove_dir_all(dir).unwrap();
+        match result {
+            Err(Error::InvalidFile { message, .. }) => {
+                assert_eq!(message, "train set has 2 images but 3 labels")
             }
-            CONFIG::TEST => {
-                // algorithmically the same as above, just with testing set
-                let mut start = rng.gen_range(0..self.testing_data.layer.ncols()) as isize;
-                let mut end = &start + &self.slice_range;
-                if (start + self.slice_range) >= (self.testing_data.layer.ncols() - 1usize) as isize
-                {
-                    end = &start - &self.slice_range;
-                }
-                if start > end {
-                    let temp = start;
-                    start = end;
-                    end = temp;
-                }
-                self.test_data_slice = Layer::dummy_layer(
-                    self.testing_data
-                        .layer
-                        .slice_axis(Axis(1), Slice::new(start, Some(end), 1))
-                        .clone()
-                        .to_owned(),
-                );
-                self.test_label_slice = Layer::dummy_layer(
-                    self.testing_labels
-                        .layer
-                        .slice_axis(Axis(1), Slice::new(start, Some(end), 1))
-                        .clone()
-                        .to_owned(),
-                );
+            _ => panic!("expected the labels to be rejected"),
+        }
+    }
+
+    #[test]
+    fn emnist_images_are_transposed_upright() {
+        let dir = write_set(
+            "transpose",
+            "emnist-balanced-train",
+            &[[0, 1, 2, 3, 4, 5], [6, 7, 8, 9, 10, 11]],
+            &[0, 46],
+        );
+        let (images, labels) =
+            Dataset::read_set(&dir, DatasetKind::EmnistBalanced, "emnist-balanced-train").unwrap();
+        fs::remove_dir_all(dir).unwrap();
+        // the 2x3 images are stored a column at a time, so they are 3x2 once upright
+        assert_eq!((images.count, images.rows, images.cols), (2, 3, 2));
+        assert_eq!(images.pixels, vec![0, 3, 1, 4, 2, 5, 6, 9, 7, 10, 8, 11]);
+        assert_eq!(labels, vec![0, 46]);
+    }
+
+    #[test]
+    fn emnist_letters_count_from_1() {
+        let dir = write_set(
+            "letters",
+            "emnist-letters-train",
+            &[[0; 6], [0; 6], [0; 6]],
+            &[1, 2, 26],
+        );
+        let (_, labels) =
+            Dataset::read_set(&dir, DatasetKind::EmnistLetters, "emnist-letters-train").unwrap();
+        fs::remove_dir_all(dir).unwrap();
+        assert_eq!(labels, vec![0, 1, 25]);
+    }
+
+    #[test]
+    fn rejects_labels_outside_the_classes() {
+        for (kind, prefix, label) in [
+            (DatasetKind::Mnist, "train", 10),
+            (DatasetKind::EmnistBalanced, "emnist-balanced-train", 47),
+            (DatasetKind::EmnistLetters, "emnist-letters-train", 0),
+            (DatasetKind::EmnistLetters, "emnist-letters-train", 27),
+        ] {
+            let dir = write_set("range", prefix, &[[0; 6], [0; 6]], &[1, label]);
+            let result = Dataset::read_set(&dir, kind, prefix);
+            fs::remove_dir_all(dir).unwrap();
+            match result {
+                Err(Error::InvalidFile { message, .. }) => assert!(
+                    message.starts_with(&format!("label {} is not one of the", label)),
+                    "{}",
+                    message
+                ),
+                _ => panic!("expected label {} of {:?} to be rejected", label, kind),
             }
         }
     }
+
+    #[test]
+    fn restored_batches_only_refer_to_their_samples() {
+        let batches = |order: Vec<usize>, batch_size| Batches {
+            order,
+            position: 0,
+            batch_size,
+        };
+        assert!(batches(vec![2, 0, 1], 2).is_valid());
+        assert!(!batches(vec![2, 0, 3], 2).is_valid());
+        assert!(!batches(vec![2, 0, 1], 0).is_valid());
+    }
+
+    #[test]
+    fn every_class_has_a_label() {
+        assert_eq!(DatasetKind::Mnist.label(7), "7");
+        assert_eq!(DatasetKind::FashionMnist.label(9), "Ankle boot");
+        assert_eq!(DatasetKind::EmnistBalanced.label(10), "A");
+        assert_eq!(DatasetKind::EmnistBalanced.label(46), "t");
+        assert_eq!(DatasetKind::EmnistLetters.label(0), "a");
+        assert_eq!(DatasetKind::EmnistLetters.label(25), "z");
+        for kind in DatasetKind::value_variants() {
+            assert!((0..kind.classes()).all(|class| !kind.label(class).is_empty()));
+        }
+    }
 }
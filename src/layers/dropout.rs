@@ -0,0 +1,177 @@
+use super::layer::{ActivationLayer, Layer, Parameter};
+use crate::checkpoint::LayerSpec;
+use crate::error::{Error, Result};
+use crate::model::CONFIG;
+use ndarray::prelude::Array2;
+use ndarray_rand::rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+// inverted dropout: while training, each node is zeroed with probability rate and the kept ones
+// are scaled by 1 / (1 - rate), so the expected output matches evaluation, where every node
+// passes through unchanged
+pub struct Dropout {
+    pub layer: Layer,
+    rate: f32,
+    // whether nodes are dropped, set by set_mode
+    training: bool,
+    // 0 for each dropped node and the scale for each kept one, reused by backward prop
+    mask: Array2<f32>,
+    // source of the masks, seeded from entropy until reseed is called
+    rng: ChaCha8Rng,
+}
+
+impl Dropout {
+    // create new dropout layer, starting in training mode
+    // nodes: size of the layer before it, which is also the size of this layer
+    // rate: probability of dropping each node
+    pub fn new(nodes: usize, rate: f32) -> Result<Dropout> {
+        if nodes == 0 {
+            return Err(Error::InvalidConfig(
+                "a Dropout layer needs at least one node".to_string(),
+            ));
+        }
+        if !(0.0..1.0).contains(&rate) {
+            return Err(Error::InvalidConfig(format!(
+                "dropout rate must be at least 0 and below 1, got {}",
+                rate
+            )));
+        }
+        // no weights or biases, the layer only holds the masked output and its gradient
+        let mut layer = Layer::new_layer(0, nodes);
+        layer.biases = Array2::<f32>::zeros((0, 1));
+        layer.d_biases = Array2::<f32>::zeros((0, 1));
+        Ok(Dropout {
+            layer,
+            rate,
+            training: true,
+            mask: Array2::<f32>::zeros((nodes, 0)),
+            rng: ChaCha8Rng::from_entropy(),
+        })
+    }
+
+    // probability of dropping each node while training
+    pub fn rate(&self) -> f32 {
+        self.rate
+    }
+}
+
+impl ActivationLayer for Dropout {
+    // apply the mask drawn in forward prop
+    fn activate(&mut self) {
+        self.layer.layer = &self.layer.preactivation * &self.mask;
+    }
+
+    // gradient only flows through the kept nodes, scaled the same way as their outputs
+    fn deactivate(&mut self, previous_layer: &Layer) {
+        self.layer.d_activation = &previous_layer.layer * &self.mask;
+    }
+
+    // draw a new mask while training, pass everything through otherwise
+    fn forward_prop(&mut self, previous_layer: &Layer) {
+        self.layer.preactivation = previous_layer.layer.clone();
+        let (rate, rng) = (self.rate, &mut self.rng);
+        self.mask = if self.training && rate > 0.0 {
+            let scale = 1f32 / (1f32 - rate);
+            Array2::from_shape_fn(previous_layer.layer.raw_dim(), |_| {
+                if rng.gen::<f32>() < rate {
+                    0f32
+                } else {
+                    scale
+                }
+            })
+        } else {
+            Array2::ones(previous_layer.layer.raw_dim())
+        };
+        self.activate();
+    }
+
+    // nothing to learn, only keep the gradient for passing it on
+    fn backward_prop(&mut self, previous_layer: &Layer, _next_layer: &Layer) {
+        self.deactivate(previous_layer);
+    }
+
+    fn input_gradient(&self) -> Array2<f32> {
+        self.layer.d_activation.clone()
+    }
+
+    // no parameters to update
+    fn parameters(&mut self) -> Vec<Parameter<'_>> {
+        Vec::new()
+    }
+
+    // only drop nodes while training
+    fn set_mode(&mut self, mode: CONFIG) {
+        self.training = matches!(mode, CONFIG::TRAIN);
+    }
+
+    fn reseed(&mut self, seed: u64) {
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+    }
+
+    fn layer(&self) -> &Layer {
+        &self.layer
+    }
+
+    fn layer_mut(&mut self) -> &mut Layer {
+        &mut self.layer
+    }
+
+    fn spec(&self) -> LayerSpec {
+        LayerSpec::Dropout {
+            nodes: self.layer.weights.nrows(),
+            rate: self.rate,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gradient_check::{check_layer, random};
+
+    #[test]
+    fn dropout_reuses_mask_in_backward_prop() {
+        let mut dropout = Dropout::new(50, 0.4).unwrap();
+        let input = Layer::dummy_layer(random((50, 4), 1));
+        dropout.forward_prop(&input);
+        let upstream = random((50, 4), 2);
+        dropout.backward_prop(&Layer::dummy_layer(upstream.clone()), &input);
+        let gradient = dropout.input_gradient();
+        let mut dropped = 0;
+        for (i, output) in dropout.layer.layer.iter().enumerate() {
+            let (row, col) = (i / 4, i % 4);
+            if *output == 0.0 {
+                dropped += 1;
+                assert_eq!(gradient[[row, col]], 0.0);
+            } else {
+                let scale = 1f32 / 0.6;
+                assert!((output - input.layer[[row, col]] * scale).abs() < 1e-6);
+                assert!((gradient[[row, col]] - upstream[[row, col]] * scale).abs() < 1e-6);
+            }
+        }
+        assert!(dropped > 0 && dropped < 200);
+    }
+
+    #[test]
+    fn dropout_masks_repeat_with_the_same_seed() {
+        let input = Layer::dummy_layer(random((20, 4), 5));
+        let mask = |seed| {
+            let mut dropout = Dropout::new(20, 0.5).unwrap();
+            dropout.reseed(seed);
+            dropout.forward_prop(&input);
+            dropout.layer.layer
+        };
+        assert_eq!(mask(6), mask(6));
+        assert_ne!(mask(6), mask(7));
+    }
+
+    #[test]
+    fn dropout_passes_through_when_evaluating() {
+        let mut dropout = Dropout::new(6, 0.5).unwrap();
+        dropout.set_mode(CONFIG::TEST);
+        let input = random((6, 3), 3);
+        dropout.forward_prop(&Layer::dummy_layer(input.clone()));
+        assert_eq!(dropout.layer.layer, input);
+        check_layer(&mut dropout, &input, 4);
+    }
+}
@@ -0,0 +1,116 @@
+use super::initializer::Initializer;
+use super::layer::{ActivationLayer, Layer};
+use crate::checkpoint::LayerSpec;
+use crate::error::{Error, Result};
+use ndarray_rand::rand::Rng;
+// implementation of the exponential linear unit, which smoothly saturates to -elu_coefficient
+// for negative inputs instead of cutting them off
+pub struct ELU {
+    pub layer: Layer,
+    elu_coefficient: f32,
+}
+
+impl ELU {
+    // create new elu layer
+    // initializer: how the weights are drawn, he normal suits this activation
+    pub fn new<R: Rng + ?Sized>(
+        input: usize,
+        nodes: usize,
+        elu_coefficient: f32,
+        initializer: Initializer,
+        rng: &mut R,
+    ) -> Result<ELU> {
+        if input == 0 || nodes == 0 {
+            return Err(Error::InvalidConfig(format!(
+                "an ELU layer needs at least one input and one node, got {} inputs and {} nodes",
+                input, nodes
+            )));
+        }
+        // create a normal layer
+        let mut layer = Layer::new_layer(input, nodes);
+        // draw the weights, the biases start at 0
+        layer.weights = initializer.initialize((nodes, input), input, nodes, rng)?;
+        layer.initializer = initializer;
+        Ok(ELU {
+            layer,
+            elu_coefficient,
+        })
+    }
+}
+
+impl ActivationLayer for ELU {
+    // activate using x for positive inputs and elu_coefficient * (e^x - 1) otherwise
+    fn activate(&mut self) {
+        let elu_coefficient = self.elu_coefficient;
+        self.layer.layer = self.layer.preactivation.map(|x| {
+            if *x > 0.0 {
+                *x
+            } else {
+                elu_coefficient * x.exp_m1()
+            }
+        });
+    }
+    // calculate gradient of the layer
+    fn deactivate(&mut self, previous_layer: &Layer) {
+        let elu_coefficient = self.elu_coefficient;
+        let derivative = self.layer.preactivation.map(|x| {
+            if *x > 0.0 {
+                1.0
+            } else {
+                elu_coefficient * x.exp()
+            }
+        });
+        self.layer.d_activation = &previous_layer.layer * &derivative;
+    }
+
+    // forward prop
+    fn forward_prop(&mut self, previous_layer: &Layer) {
+        self.layer.forward_prop(previous_layer);
+        self.activate();
+    }
+
+    // backwards prop
+    fn backward_prop(&mut self, previous_layer: &Layer, next_layer: &Layer) {
+        self.deactivate(previous_layer);
+        self.layer.backward_prop(next_layer);
+    }
+
+    fn layer(&self) -> &Layer {
+        &self.layer
+    }
+
+    fn layer_mut(&mut self) -> &mut Layer {
+        &mut self.layer
+    }
+
+    fn spec(&self) -> LayerSpec {
+        LayerSpec::ELU {
+            input: self.layer.weights.ncols(),
+            nodes: self.layer.weights.nrows(),
+            elu_coefficient: self.elu_coefficient,
+            initializer: self.layer.initializer,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gradient_check::{check_layer, random};
+    use ndarray_rand::rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn elu_gradients() {
+        let mut elu = ELU::new(
+            6,
+            4,
+            1.0,
+            Initializer::HeNormal,
+            &mut StdRng::seed_from_u64(0),
+        )
+        .unwrap();
+        elu.layer.weights = random((4, 6), 1);
+        elu.layer.biases = random((4, 1), 2);
+        check_layer(&mut elu, &random((6, 3), 3), 4);
+    }
+}
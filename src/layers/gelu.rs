@@ -0,0 +1,102 @@
+use super::initializer::Initializer;
+use super::layer::{ActivationLayer, Layer};
+use crate::checkpoint::LayerSpec;
+use crate::error::{Error, Result};
+use ndarray_rand::rand::Rng;
+
+// sqrt(2 / pi), used by the tanh approximation of the gaussian cdf
+const SQRT_2_OVER_PI: f32 = 0.797_884_6;
+// coefficient of the cubic term of the tanh approximation
+const CUBIC: f32 = 0.044715;
+
+// implementation of the gaussian error linear unit, x * P(X <= x) for a standard normal X,
+// using the common tanh approximation
+pub struct GELU {
+    pub layer: Layer,
+}
+
+impl GELU {
+    // create new gelu layer
+    // initializer: how the weights are drawn, he normal suits this activation
+    pub fn new<R: Rng + ?Sized>(
+        input: usize,
+        nodes: usize,
+        initializer: Initializer,
+        rng: &mut R,
+    ) -> Result<GELU> {
+        if input == 0 || nodes == 0 {
+            return Err(Error::InvalidConfig(format!(
+                "a GELU layer needs at least one input and one node, got {} inputs and {} nodes",
+                input, nodes
+            )));
+        }
+        // create a normal layer
+        let mut layer = Layer::new_layer(input, nodes);
+        // draw the weights, the biases start at 0
+        layer.weights = initializer.initialize((nodes, input), input, nodes, rng)?;
+        layer.initializer = initializer;
+        Ok(GELU { layer })
+    }
+}
+
+impl ActivationLayer for GELU {
+    // activate using 0.5 * x * (1 + tanh(sqrt(2 / pi) * (x + 0.044715 * x^3)))
+    fn activate(&mut self) {
+        self.layer.layer = self.layer.preactivation.map(|x| {
+            let t = (SQRT_2_OVER_PI * (x + CUBIC * x.powi(3))).tanh();
+            0.5 * x * (1.0 + t)
+        });
+    }
+    // calculate gradient of the layer
+    fn deactivate(&mut self, previous_layer: &Layer) {
+        let derivative = self.layer.preactivation.map(|x| {
+            let t = (SQRT_2_OVER_PI * (x + CUBIC * x.powi(3))).tanh();
+            0.5 * (1.0 + t) + 0.5 * x * (1.0 - t * t) * SQRT_2_OVER_PI * (1.0 + 3.0 * CUBIC * x * x)
+        });
+        self.layer.d_activation = &previous_layer.layer * &derivative;
+    }
+
+    // forward prop
+    fn forward_prop(&mut self, previous_layer: &Layer) {
+        self.layer.forward_prop(previous_layer);
+        self.activate();
+    }
+
+    // backwards prop
+    fn backward_prop(&mut self, previous_layer: &Layer, next_layer: &Layer) {
+        self.deactivate(previous_layer);
+        self.layer.backward_prop(next_layer);
+    }
+
+    fn layer(&self) -> &Layer {
+        &self.layer
+    }
+
+    fn layer_mut(&mut self) -> &mut Layer {
+        &mut self.layer
+    }
+
+    fn spec(&self) -> LayerSpec {
+        LayerSpec::GELU {
+            input: self.layer.weights.ncols(),
+            nodes: self.layer.weights.nrows(),
+            initializer: self.layer.initializer,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gradient_check::{check_layer, random};
+    use ndarray_rand::rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn gelu_gradients() {
+        let mut gelu =
+            GELU::new(6, 4, Initializer::HeNormal, &mut StdRng::seed_from_u64(0)).unwrap();
+        gelu.layer.weights = random((4, 6), 1);
+        gelu.layer.biases = random((4, 1), 2);
+        check_layer(&mut gelu, &random((6, 3), 3), 4);
+    }
+}
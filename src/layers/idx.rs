@@ -0,0 +1,219 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+
+use crate::error::{Error, Result};
+
+// magic numbers of the idx files used by mnist, unsigned bytes with 1 or 3 dimensions
+const IDX1_MAGIC: u32 = 0x0000_0801;
+const IDX3_MAGIC: u32 = 0x0000_0803;
+// first two bytes of every gzip file
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+// images read from an idx3 file, stored one after another in row major order
+pub struct IdxImages {
+    pub count: usize,
+    pub rows: usize,
+    pub cols: usize,
+    pub pixels: Vec<u8>,
+}
+
+// read an idx3 image file, decompressing it first if it is gzipped
+pub fn read_images(path: &Path) -> Result<IdxImages> {
+    let bytes = read_file(path)?;
+    let header = parse_header(path, &bytes, IDX3_MAGIC, 3)?;
+    let (count, rows, cols) = (header[0], header[1], header[2]);
+    // a corrupt header can describe more pixels than fit in memory, or even in a usize
+    let length = count
+        .checked_mul(rows)
+        .and_then(|length| length.checked_mul(cols))
+        .ok_or_else(|| {
+            Error::invalid_file(
+                path,
+                format!(
+                    "header describes {} images of {}x{} pixels, which is too many",
+                    count, rows, cols
+                ),
+            )
+        })?;
+    let pixels = check_body(path, &bytes, 16, length)?;
+    Ok(IdxImages {
+        count,
+        rows,
+        cols,
+        pixels,
+    })
+}
+
+// read an idx1 label file, decompressing it first if it is gzipped
+pub fn read_labels(path: &Path) -> Result<Vec<u8>> {
+    let bytes = read_file(path)?;
+    let header = parse_header(path, &bytes, IDX1_MAGIC, 1)?;
+    check_body(path, &bytes, 8, header[0])
+}
+
+// find an idx file in a directory, either uncompressed or with a .gz extension
+pub fn find_file(directory: &Path, name: &str) -> Result<PathBuf> {
+    let plain = directory.join(name);
+    let gzipped = directory.join(format!("{}.gz", name));
+    if plain.is_file() {
+        Ok(plain)
+    } else if gzipped.is_file() {
+        Ok(gzipped)
+    } else {
+        Err(Error::invalid_file(
+            directory,
+            format!("could not find {} or {}.gz", name, name),
+        ))
+    }
+}
+
+// read the whole file into memory, gunzipping it when it starts with the gzip magic number
+fn read_file(path: &Path) -> Result<Vec<u8>> {
+    let mut raw = Vec::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut raw))
+        .map_err(|err| Error::io(path, err))?;
+    if raw.starts_with(&GZIP_MAGIC) {
+        let mut decoded = Vec::new();
+        GzDecoder::new(&raw[..])
+            .read_to_end(&mut decoded)
+            .map_err(|err| Error::invalid_file(path, format!("could not decompress: {}", err)))?;
+        Ok(decoded)
+    } else {
+        Ok(raw)
+    }
+}
+
+// validate the magic number and return the size of each dimension
+fn parse_header(path: &Path, bytes: &[u8], magic: u32, dimensions: usize) -> Result<Vec<usize>> {
+    let header_length = 4 + 4 * dimensions;
+    if bytes.len() < header_length {
+        return Err(Error::invalid_file(
+            path,
+            "file is too short to hold an idx header",
+        ));
+    }
+    let found = read_u32(bytes, 0);
+    if found != magic {
+        return Err(Error::invalid_file(
+            path,
+            format!(
+                "expected magic number {:#010x}, found {:#010x}",
+                magic, found
+            ),
+        ));
+    }
+    let sizes: Vec<usize> = (0..dimensions)
+        .map(|i| read_u32(bytes, 4 + 4 * i) as usize)
+        .collect();
+    if sizes.contains(&0) {
+        return Err(Error::invalid_file(
+            path,
+            format!("empty dimension in {:?}", sizes),
+        ));
+    }
+    Ok(sizes)
+}
+
+// make sure the data after the header is exactly as long as the header says, before copying it
+fn check_body(path: &Path, bytes: &[u8], offset: usize, length: usize) -> Result<Vec<u8>> {
+    let found = bytes.len() - offset;
+    if found != length {
+        return Err(Error::invalid_file(
+            path,
+            format!("header describes {} bytes of data, found {}", length, found),
+        ));
+    }
+    Ok(bytes[offset..].to_vec())
+}
+
+// big endian u32 at an offset
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // write an idx file with a header of big endian u32s followed by the data
+    fn write_idx(name: &str, header: &[u32], data: &[u8]) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("rustnist-idx-{}-{}", name, std::process::id()));
+        let mut bytes: Vec<u8> = header
+            .iter()
+            .flat_map(|value| value.to_be_bytes())
+            .collect();
+        bytes.extend_from_slice(data);
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    // read a file, remove it and return the message of the error it was rejected with
+    fn rejection<T>(path: PathBuf, read: fn(&Path) -> Result<T>) -> String {
+        let result = read(&path);
+        fs::remove_file(&path).unwrap();
+        match result {
+            Err(Error::InvalidFile { message, .. }) => message,
+            Err(err) => panic!("expected an invalid file, got {}", err),
+            Ok(_) => panic!("expected an invalid file"),
+        }
+    }
+
+    #[test]
+    fn reads_images_and_labels() {
+        let path = write_idx(
+            "images",
+            &[IDX3_MAGIC, 2, 2, 3],
+            &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12],
+        );
+        let images = read_images(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!((images.count, images.rows, images.cols), (2, 2, 3));
+        assert_eq!(images.pixels, (1..=12).collect::<Vec<u8>>());
+
+        let path = write_idx("labels", &[IDX1_MAGIC, 3], &[7, 0, 9]);
+        let labels = read_labels(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(labels, vec![7, 0, 9]);
+    }
+
+    #[test]
+    fn rejects_a_bad_magic_number() {
+        // a label file given where images are expected
+        let path = write_idx("magic", &[IDX1_MAGIC, 2, 1, 1], &[0, 0]);
+        assert!(rejection(path, read_images).contains("magic number"));
+        let path = write_idx("short", &[IDX3_MAGIC], &[]);
+        assert!(rejection(path, read_labels).contains("too short"));
+    }
+
+    #[test]
+    fn rejects_a_truncated_file() {
+        let path = write_idx("truncated-images", &[IDX3_MAGIC, 2, 2, 2], &[0; 7]);
+        assert!(rejection(path, read_images).contains("8 bytes of data, found 7"));
+        let path = write_idx("truncated-labels", &[IDX1_MAGIC, 4], &[1, 2, 3]);
+        assert!(rejection(path, read_labels).contains("4 bytes of data, found 3"));
+    }
+
+    #[test]
+    fn rejects_dimensions_that_overflow() {
+        let path = write_idx(
+            "overflow",
+            &[IDX3_MAGIC, u32::MAX, u32::MAX, u32::MAX],
+            &[0; 16],
+        );
+        assert!(rejection(path, read_images).contains("too many"));
+        // dimensions that fit in a usize are still checked against the file before allocating
+        let path = write_idx("huge", &[IDX3_MAGIC, u32::MAX, 28, 28], &[0; 16]);
+        assert!(rejection(path, read_images).contains("found 16"));
+    }
+}
@@ -0,0 +1,231 @@
+use std::fmt;
+use std::str::FromStr;
+
+use ndarray::{prelude::Array2, Array};
+use ndarray_rand::rand::Rng;
+use ndarray_rand::rand_distr::{Normal, Uniform};
+use ndarray_rand::RandomExt;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+// strategies for drawing the initial weights of a layer
+// fan_in is the amount of inputs of each node, fan_out the amount of nodes each input feeds
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Initializer {
+    // normal with variance 2 / fan_in, for relu and its relatives
+    HeNormal,
+    // uniform with the same variance as HeNormal
+    HeUniform,
+    // normal with variance 2 / (fan_in + fan_out), for saturating activations like tanh
+    XavierNormal,
+    // uniform with the same variance as XavierNormal
+    XavierUniform,
+    // normal with variance 1 / fan_in
+    LecunNormal,
+    // uniform with the same variance as LecunNormal
+    LecunUniform,
+    // random matrix with orthonormal rows or columns, whichever there are fewer of
+    Orthogonal,
+    // every weight 0
+    Zeros,
+    // normal with a chosen standard deviation
+    Normal { std: f32 },
+    // uniform between -limit and limit
+    Uniform { limit: f32 },
+}
+
+impl Initializer {
+    // draw a rows x cols weight matrix
+    pub fn initialize<R: Rng + ?Sized>(
+        &self,
+        shape: (usize, usize),
+        fan_in: usize,
+        fan_out: usize,
+        rng: &mut R,
+    ) -> Result<Array2<f32>> {
+        let (fan_in, fan_out) = (fan_in as f32, fan_out as f32);
+        match *self {
+            Initializer::HeNormal => normal(shape, (2f32 / fan_in).sqrt(), rng),
+            Initializer::HeUniform => uniform(shape, (6f32 / fan_in).sqrt(), rng),
+            Initializer::XavierNormal => normal(shape, (2f32 / (fan_in + fan_out)).sqrt(), rng),
+            Initializer::XavierUniform => uniform(shape, (6f32 / (fan_in + fan_out)).sqrt(), rng),
+            Initializer::LecunNormal => normal(shape, (1f32 / fan_in).sqrt(), rng),
+            Initializer::LecunUniform => uniform(shape, (3f32 / fan_in).sqrt(), rng),
+            Initializer::Orthogonal => orthogonal(shape, rng),
+            Initializer::Zeros => Ok(Array2::<f32>::zeros(shape)),
+            Initializer::Normal { std } => normal(shape, std, rng),
+            Initializer::Uniform { limit } => uniform(shape, limit, rng),
+        }
+    }
+}
+
+fn normal<R: Rng + ?Sized>(shape: (usize, usize), std: f32, rng: &mut R) -> Result<Array2<f32>> {
+    if !(std >= 0.0 && std.is_finite()) {
+        return Err(Error::InvalidConfig(format!(
+            "normal initialization needs a standard deviation of at least 0, got {}",
+            std
+        )));
+    }
+    let distribution =
+        Normal::new(0.0f32, std).map_err(|err| Error::InvalidConfig(err.to_string()))?;
+    Ok(Array::random_using(shape, distribution, rng))
+}
+
+fn uniform<R: Rng + ?Sized>(shape: (usize, usize), limit: f32, rng: &mut R) -> Result<Array2<f32>> {
+    if !(limit > 0.0 && limit.is_finite()) {
+        return Err(Error::InvalidConfig(format!(
+            "uniform initialization needs a positive limit, got {}",
+            limit
+        )));
+    }
+    Ok(Array::random_using(
+        shape,
+        Uniform::new_inclusive(-limit, limit),
+        rng,
+    ))
+}
+
+// orthonormalize the rows of a random normal matrix with gram-schmidt, transposed when there are
+// more rows than columns since at most cols rows can be orthonormal
+fn orthogonal<R: Rng + ?Sized>(shape: (usize, usize), rng: &mut R) -> Result<Array2<f32>> {
+    let (rows, cols) = shape;
+    let transposed = rows > cols;
+    let mut matrix = normal(if transposed { (cols, rows) } else { shape }, 1f32, rng)?;
+    for i in 0..matrix.nrows() {
+        for j in 0..i {
+            let previous = matrix.row(j).to_owned();
+            let projection = matrix.row(i).dot(&previous);
+            matrix.row_mut(i).scaled_add(-projection, &previous);
+        }
+        let norm = matrix.row(i).dot(&matrix.row(i)).sqrt();
+        if norm > 0.0 {
+            matrix.row_mut(i).mapv_inplace(|x| x / norm);
+        }
+    }
+    Ok(if transposed {
+        // copied into row major order, like the other initializers produce
+        matrix.reversed_axes().as_standard_layout().into_owned()
+    } else {
+        matrix
+    })
+}
+
+// parse an initializer from the command line, a name like he-normal or a custom distribution like
+// normal:0.05 or uniform:0.1
+impl FromStr for Initializer {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Initializer, String> {
+        let parameter = |value: &str| {
+            value
+                .parse::<f32>()
+                .map_err(|err| format!("invalid parameter {}: {}", value, err))
+        };
+        Ok(match s.split_once(':') {
+            Some(("normal", std)) => Initializer::Normal {
+                std: parameter(std)?,
+            },
+            Some(("uniform", limit)) => Initializer::Uniform {
+                limit: parameter(limit)?,
+            },
+            _ => match s {
+                "he-normal" => Initializer::HeNormal,
+                "he-uniform" => Initializer::HeUniform,
+                "xavier-normal" | "glorot-normal" => Initializer::XavierNormal,
+                "xavier-uniform" | "glorot-uniform" => Initializer::XavierUniform,
+                "lecun-normal" => Initializer::LecunNormal,
+                "lecun-uniform" => Initializer::LecunUniform,
+                "orthogonal" => Initializer::Orthogonal,
+                "zeros" => Initializer::Zeros,
+                _ => {
+                    return Err(format!(
+                        "unknown initializer {}, expected one of he-normal, he-uniform, \
+                         xavier-normal, xavier-uniform, lecun-normal, lecun-uniform, orthogonal, \
+                         zeros, normal:STD or uniform:LIMIT",
+                        s
+                    ))
+                }
+            },
+        })
+    }
+}
+
+// the name FromStr parses
+impl fmt::Display for Initializer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Initializer::HeNormal => write!(f, "he-normal"),
+            Initializer::HeUniform => write!(f, "he-uniform"),
+            Initializer::XavierNormal => write!(f, "xavier-normal"),
+            Initializer::XavierUniform => write!(f, "xavier-uniform"),
+            Initializer::LecunNormal => write!(f, "lecun-normal"),
+            Initializer::LecunUniform => write!(f, "lecun-uniform"),
+            Initializer::Orthogonal => write!(f, "orthogonal"),
+            Initializer::Zeros => write!(f, "zeros"),
+            Initializer::Normal { std } => write!(f, "normal:{}", std),
+            Initializer::Uniform { limit } => write!(f, "uniform:{}", limit),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray_rand::rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn he_normal_has_variance_two_over_fan_in() {
+        let weights = Initializer::HeNormal
+            .initialize((200, 100), 100, 200, &mut StdRng::seed_from_u64(1))
+            .unwrap();
+        let variance = weights.map(|x| x * x).mean().unwrap();
+        assert!((variance - 0.02).abs() < 0.002);
+    }
+
+    #[test]
+    fn orthogonal_rows_and_columns() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for shape in [(4, 7), (7, 4)] {
+            let weights = Initializer::Orthogonal
+                .initialize(shape, shape.1, shape.0, &mut rng)
+                .unwrap();
+            assert_eq!(weights.dim(), shape);
+            let gram = if shape.0 <= shape.1 {
+                weights.dot(&weights.t())
+            } else {
+                weights.t().dot(&weights)
+            };
+            for ((i, j), value) in gram.indexed_iter() {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((value - expected).abs() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_same_weights() {
+        let draw = |seed| {
+            Initializer::XavierUniform
+                .initialize((5, 3), 3, 5, &mut StdRng::seed_from_u64(seed))
+                .unwrap()
+        };
+        assert_eq!(draw(3), draw(3));
+        assert_ne!(draw(3), draw(4));
+    }
+
+    #[test]
+    fn parse_initializers() {
+        for initializer in [
+            Initializer::HeUniform,
+            Initializer::Orthogonal,
+            Initializer::Normal { std: 0.05 },
+            Initializer::Uniform { limit: 0.1 },
+        ] {
+            assert_eq!(initializer.to_string().parse(), Ok(initializer));
+        }
+        assert_eq!("glorot-normal".parse(), Ok(Initializer::XavierNormal));
+        assert!("normal:x".parse::<Initializer>().is_err());
+        assert!("kaiming".parse::<Initializer>().is_err());
+    }
+}
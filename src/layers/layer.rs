@@ -1,9 +1,95 @@
-use ndarray::{prelude::Array2, Axis, Zip};
-extern crate blas_src;
-// trait used to enforce activation and deactivation
+use super::initializer::Initializer;
+use crate::checkpoint::{LayerCheckpoint, LayerSpec};
+use crate::model::CONFIG;
+use crate::optimizer::{Optimizer, OptimizerState};
+use ndarray::{prelude::Array2, Axis};
+// trait used to enforce activation and deactivation, and to let the model chain layers together
 pub trait ActivationLayer {
     fn activate(&mut self);
+    // previous_layer holds the gradient of the loss with respect to this layer's output
     fn deactivate(&mut self, previous_layer: &Layer);
+    // calculate this layer's output from the output of the layer before it
+    fn forward_prop(&mut self, previous_layer: &Layer);
+    // calculate this layer's gradients, previous_layer is the gradient flowing back into this
+    // layer and next_layer is the layer that fed into it during forward prop
+    fn backward_prop(&mut self, previous_layer: &Layer, next_layer: &Layer);
+    // calculate this layer's gradients from the gradient of the loss with respect to its
+    // preactivation instead of its output, for an output layer whose activation is fused with the
+    // loss like softmax with cross entropy
+    fn backward_prop_preactivation(&mut self, gradient: &Layer, next_layer: &Layer) {
+        self.layer_mut().d_activation = gradient.layer.clone();
+        self.layer_mut().backward_prop(next_layer);
+    }
+    // access to the underlying layer
+    fn layer(&self) -> &Layer;
+    fn layer_mut(&mut self) -> &mut Layer;
+    // description of the layer used to rebuild it from a checkpoint
+    fn spec(&self) -> LayerSpec;
+
+    // gradient of the loss with respect to this layer's input, passed to the layer before it
+    fn input_gradient(&self) -> Array2<f32> {
+        self.layer().weights.t().dot(&self.layer().d_activation)
+    }
+
+    // every parameter the optimizer updates, the weights and biases for most layers
+    fn parameters(&mut self) -> Vec<Parameter<'_>> {
+        self.layer_mut().parameters()
+    }
+
+    // update every parameter with its gradient
+    fn update_params(&mut self, optimizer: &Optimizer) {
+        for parameter in self.parameters() {
+            optimizer.update(parameter.value, parameter.gradient, parameter.state);
+        }
+    }
+
+    // switch between training and evaluating, only layers that behave differently while
+    // training, like dropout, need to do anything
+    fn set_mode(&mut self, _mode: CONFIG) {}
+
+    // restart the random numbers the layer draws while training, like the masks of dropout, from
+    // a seed so runs can be reproduced
+    fn reseed(&mut self, _seed: u64) {}
+
+    // copy out the description and parameters of the layer
+    fn checkpoint(&self) -> LayerCheckpoint {
+        LayerCheckpoint {
+            spec: self.spec(),
+            weights: self.layer().weights.clone(),
+            biases: self.layer().biases.clone(),
+            buffers: Vec::new(),
+        }
+    }
+
+    // overwrite the parameters of the layer with ones from a checkpoint
+    fn load_checkpoint(&mut self, checkpoint: &LayerCheckpoint) {
+        self.layer_mut().weights = checkpoint.weights.clone();
+        self.layer_mut().biases = checkpoint.biases.clone();
+    }
+
+    // copy out the optimizer state of every parameter of the layer, for resuming training
+    fn optimizer_states(&self) -> Vec<OptimizerState> {
+        vec![
+            self.layer().weights_state.clone(),
+            self.layer().biases_state.clone(),
+        ]
+    }
+
+    // restore optimizer state copied out by optimizer_states
+    fn load_optimizer_states(&mut self, states: &[OptimizerState]) {
+        if let [weights, biases] = states {
+            self.layer_mut().weights_state = weights.clone();
+            self.layer_mut().biases_state = biases.clone();
+        }
+    }
+}
+
+// a parameter of a layer along with its gradient from the last backward prop and the state the
+// optimizer keeps for it
+pub struct Parameter<'a> {
+    pub value: &'a mut Array2<f32>,
+    pub gradient: &'a Array2<f32>,
+    pub state: &'a mut OptimizerState,
 }
 
 // struct for a layer (loosely defined)
@@ -23,19 +109,21 @@ pub struct Layer {
     pub biases: Array2<f32>,
     // derivative of bias terms
     pub d_biases: Array2<f32>,
-    // learning_rate of network
-    alpha: f32,
-    // amount of samples for each forward and backwards pass
-    samples: usize,
+    // optimizer state for the weights and biases
+    pub weights_state: OptimizerState,
+    pub biases_state: OptimizerState,
+    // how the weights were drawn, zeros until a layer draws them
+    pub initializer: Initializer,
 }
 
 impl Layer {
-    // create a new layer with all elements initialized to zero except for samples and alpha
-    pub fn new_layer(input: usize, nodes: usize, samples: usize, alpha: f32) -> Layer {
-        // preactivation, layer, and derivative of the layer are the same size
-        let preactivation = Array2::zeros((nodes, samples));
-        let layer = Array2::zeros((nodes, samples));
-        let d_activation = Array2::zeros((nodes, samples));
+    // create a new layer with all elements initialized to zero
+    // preactivation, layer, and derivative of the layer start without samples and take the size
+    // of each batch passed through them
+    pub fn new_layer(input: usize, nodes: usize) -> Layer {
+        let preactivation = Array2::zeros((nodes, 0));
+        let layer = Array2::zeros((nodes, 0));
+        let d_activation = Array2::zeros((nodes, 0));
         // weights are used in between layers
         let weights = Array2::<f32>::zeros((nodes, input));
         let d_weights = Array2::<f32>::zeros((nodes, input));
@@ -51,8 +139,9 @@ impl Layer {
             d_weights,
             biases,
             d_biases,
-            alpha,
-            samples,
+            weights_state: OptimizerState::new(),
+            biases_state: OptimizerState::new(),
+            initializer: Initializer::Zeros,
         }
     }
 
@@ -61,14 +150,15 @@ impl Layer {
     pub fn dummy_layer(layer: Array2<f32>) -> Layer {
         Layer {
             preactivation: Array2::<f32>::zeros((1, 1)),
-            layer: layer,
+            layer,
             d_activation: Array2::<f32>::zeros((1, 1)),
             weights: Array2::<f32>::zeros((1, 1)),
             d_weights: Array2::<f32>::zeros((1, 1)),
             biases: Array2::<f32>::zeros((1, 1)),
             d_biases: Array2::<f32>::zeros((1, 1)),
-            alpha: 0.0,
-            samples: 0,
+            weights_state: OptimizerState::new(),
+            biases_state: OptimizerState::new(),
+            initializer: Initializer::Zeros,
         }
     }
 
@@ -77,33 +167,42 @@ impl Layer {
         self.preactivation = &self.weights.dot(&previous_layer.layer) + &self.biases;
     }
 
-    // calculate derivative of weights and biases based on derivative of the activation
+    // calculate derivative of weights and biases based on derivative of the activation, averaged
+    // over the samples in the batch
     pub fn backward_prop(&mut self, previous_layer: &Layer) {
+        let samples = previous_layer.layer.ncols() as f32;
         self.d_weights = self
             .d_activation
             .dot(&previous_layer.layer.t())
-            .map(|x| x * 1.0 / self.samples as f32);
+            .map(|x| x * 1.0 / samples);
         self.d_biases = self
             .d_activation
             .sum_axis(Axis(1))
-            .map(|x| x * 1.0 / (self.samples as f32))
+            .map(|x| x * 1.0 / samples)
             .insert_axis(Axis(1));
     }
 
-    // update weights and biases
-    pub fn update_params(&mut self) {
-        Zip::from(&mut self.weights)
-            .and(&self.d_weights)
-            .for_each(|a, b| *a -= self.alpha * *b);
-        Zip::from(&mut self.biases)
-            .and(&self.d_biases)
-            .for_each(|a, b| *a -= self.alpha * *b);
+    // weights and biases, with their gradients and optimizer states
+    pub fn parameters(&mut self) -> Vec<Parameter<'_>> {
+        vec![
+            Parameter {
+                value: &mut self.weights,
+                gradient: &self.d_weights,
+                state: &mut self.weights_state,
+            },
+            Parameter {
+                value: &mut self.biases,
+                gradient: &self.d_biases,
+                state: &mut self.biases_state,
+            },
+        ]
     }
 
     // create a dummy one hot encoded layer, primarily used for conversion of 1D labels to
     // 2D array that can be used in backwards propogation
-    pub fn one_hot(input: &Array2<f32>) -> Layer {
-        let mut output_one_hot = Array2::<f32>::zeros((10, input.ncols()));
+    // classes: amount of rows, labels have to be below it
+    pub fn one_hot(input: &Array2<f32>, classes: usize) -> Layer {
+        let mut output_one_hot = Array2::<f32>::zeros((classes, input.ncols()));
         for ((_, j), value) in input.indexed_iter() {
             output_one_hot[[*value as usize, j]] = 1f32;
         }
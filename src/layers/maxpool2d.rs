@@ -0,0 +1,170 @@
+use super::layer::{ActivationLayer, Layer, Parameter};
+use crate::checkpoint::LayerSpec;
+use crate::error::{Error, Result};
+use ndarray::{prelude::Array2, Axis};
+
+// 2d max pooling over non overlapping size x size windows, with no parameters to learn
+// uses the same column layout as Conv2d, trailing rows and columns that do not fill a window
+// are dropped
+pub struct MaxPool2d {
+    pub layer: Layer,
+    channels: usize,
+    height: usize,
+    width: usize,
+    size: usize,
+    // index into the input column of the max of each window, for routing the gradient back
+    switches: Array2<usize>,
+}
+
+impl MaxPool2d {
+    // create new max pooling layer
+    // channels, height, width: shape of each input sample
+    // size: width and height of each window, also used as the stride
+    pub fn new(channels: usize, height: usize, width: usize, size: usize) -> Result<MaxPool2d> {
+        if channels == 0 || size == 0 || size > height || size > width {
+            return Err(Error::InvalidConfig(format!(
+                "{}x{} pooling does not fit {} channels of {}x{}",
+                size, size, channels, height, width
+            )));
+        }
+        let nodes = channels * (height / size) * (width / size);
+        // no weights or biases, the layer only holds the pooled output and its gradient
+        let mut layer = Layer::new_layer(0, nodes);
+        layer.biases = Array2::<f32>::zeros((0, 1));
+        layer.d_biases = Array2::<f32>::zeros((0, 1));
+        Ok(MaxPool2d {
+            layer,
+            channels,
+            height,
+            width,
+            size,
+            switches: Array2::<usize>::zeros((nodes, 0)),
+        })
+    }
+
+    // height of each pooled feature map
+    pub fn out_height(&self) -> usize {
+        self.height / self.size
+    }
+
+    // width of each pooled feature map
+    pub fn out_width(&self) -> usize {
+        self.width / self.size
+    }
+}
+
+impl ActivationLayer for MaxPool2d {
+    // pooling has no activation, the output is the max of each window
+    fn activate(&mut self) {
+        self.layer.layer = self.layer.preactivation.clone();
+    }
+
+    // gradient passes through unchanged to the max of each window
+    fn deactivate(&mut self, previous_layer: &Layer) {
+        self.layer.d_activation = previous_layer.layer.clone();
+    }
+
+    // take the max of each window, remembering where it came from
+    fn forward_prop(&mut self, previous_layer: &Layer) {
+        let (out_height, out_width) = (self.out_height(), self.out_width());
+        let samples = previous_layer.layer.ncols();
+        let nodes = self.channels * out_height * out_width;
+        let mut preactivation = Array2::<f32>::zeros((nodes, samples));
+        let mut switches = Array2::<usize>::zeros((nodes, samples));
+        for (j, sample) in previous_layer.layer.axis_iter(Axis(1)).enumerate() {
+            for c in 0..self.channels {
+                for y in 0..out_height {
+                    for x in 0..out_width {
+                        let node = (c * out_height + y) * out_width + x;
+                        let mut max_index = 0;
+                        let mut max_value = f32::NEG_INFINITY;
+                        for wy in 0..self.size {
+                            for wx in 0..self.size {
+                                let index = (c * self.height + y * self.size + wy) * self.width
+                                    + x * self.size
+                                    + wx;
+                                if sample[index] > max_value {
+                                    max_index = index;
+                                    max_value = sample[index];
+                                }
+                            }
+                        }
+                        preactivation[[node, j]] = max_value;
+                        switches[[node, j]] = max_index;
+                    }
+                }
+            }
+        }
+        self.layer.preactivation = preactivation;
+        self.switches = switches;
+        self.activate();
+    }
+
+    // nothing to learn, only keep the gradient for passing it on
+    fn backward_prop(&mut self, previous_layer: &Layer, _next_layer: &Layer) {
+        self.deactivate(previous_layer);
+    }
+
+    // route each gradient to the input that was the max of its window
+    fn input_gradient(&self) -> Array2<f32> {
+        let mut gradient = Array2::<f32>::zeros((
+            self.channels * self.height * self.width,
+            self.layer.d_activation.ncols(),
+        ));
+        for ((node, j), d) in self.layer.d_activation.indexed_iter() {
+            gradient[[self.switches[[node, j]], j]] += d;
+        }
+        gradient
+    }
+
+    // no parameters to update
+    fn parameters(&mut self) -> Vec<Parameter<'_>> {
+        Vec::new()
+    }
+
+    fn layer(&self) -> &Layer {
+        &self.layer
+    }
+
+    fn layer_mut(&mut self) -> &mut Layer {
+        &mut self.layer
+    }
+
+    fn spec(&self) -> LayerSpec {
+        LayerSpec::MaxPool2d {
+            channels: self.channels,
+            height: self.height,
+            width: self.width,
+            size: self.size,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray_rand::rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+    use crate::gradient_check::check_layer;
+
+    // inputs spaced further apart than the gradient check's step, so no perturbation changes
+    // which input is the max of its window
+    fn distinct_input(rows: usize, cols: usize, seed: u64) -> Array2<f32> {
+        let mut values: Vec<f32> = (0..rows * cols).map(|i| i as f32 * 0.01).collect();
+        values.shuffle(&mut StdRng::seed_from_u64(seed));
+        Array2::from_shape_vec((rows, cols), values).unwrap()
+    }
+
+    #[test]
+    fn maxpool2d_gradients() {
+        let mut pool = MaxPool2d::new(2, 4, 4, 2).unwrap();
+        check_layer(&mut pool, &distinct_input(2 * 4 * 4, 3, 1), 2);
+    }
+
+    #[test]
+    fn maxpool2d_drops_partial_windows() {
+        let mut pool = MaxPool2d::new(2, 5, 5, 2).unwrap();
+        assert_eq!((pool.out_height(), pool.out_width()), (2, 2));
+        check_layer(&mut pool, &distinct_input(2 * 5 * 5, 3, 3), 4);
+    }
+}
@@ -0,0 +1,210 @@
+use super::initializer::Initializer;
+use super::layer::{ActivationLayer, Layer, Parameter};
+use crate::checkpoint::{LayerCheckpoint, LayerSpec};
+use crate::error::{Error, Result};
+use crate::optimizer::OptimizerState;
+use ndarray::{prelude::Array2, Axis};
+use ndarray_rand::rand::Rng;
+
+// slope every node starts with for negative inputs
+const INITIAL_SLOPE: f32 = 0.25;
+
+// implementation of parametric relu, a leaky relu whose slope for negative inputs is learned
+// separately for every node
+pub struct PReLU {
+    pub layer: Layer,
+    // nodes x 1 slope of each node for negative inputs, and its derivative
+    pub slopes: Array2<f32>,
+    pub d_slopes: Array2<f32>,
+    // optimizer state for the slopes
+    pub slopes_state: OptimizerState,
+}
+
+impl PReLU {
+    // create new prelu layer
+    // initializer: how the weights are drawn, he normal suits this activation
+    pub fn new<R: Rng + ?Sized>(
+        input: usize,
+        nodes: usize,
+        initializer: Initializer,
+        rng: &mut R,
+    ) -> Result<PReLU> {
+        if input == 0 || nodes == 0 {
+            return Err(Error::InvalidConfig(format!(
+                "a PReLU layer needs at least one input and one node, got {} inputs and {} nodes",
+                input, nodes
+            )));
+        }
+        // create a normal layer
+        let mut layer = Layer::new_layer(input, nodes);
+        // draw the weights, the biases start at 0
+        layer.weights = initializer.initialize((nodes, input), input, nodes, rng)?;
+        layer.initializer = initializer;
+        Ok(PReLU {
+            layer,
+            slopes: Array2::<f32>::from_elem((nodes, 1), INITIAL_SLOPE),
+            d_slopes: Array2::<f32>::zeros((nodes, 1)),
+            slopes_state: OptimizerState::new(),
+        })
+    }
+}
+
+impl ActivationLayer for PReLU {
+    // activate using x for positive inputs and slope * x otherwise
+    fn activate(&mut self) {
+        let mut out = self.layer.preactivation.clone();
+        for ((i, _), item) in out.indexed_iter_mut() {
+            if *item < 0.0 {
+                *item *= self.slopes[[i, 0]];
+            }
+        }
+        self.layer.layer = out;
+    }
+    // calculate gradient of the layer
+    fn deactivate(&mut self, previous_layer: &Layer) {
+        let mut derivative = Array2::<f32>::ones(self.layer.preactivation.raw_dim());
+        for ((i, j), item) in self.layer.preactivation.indexed_iter() {
+            if *item <= 0.0 {
+                derivative[[i, j]] = self.slopes[[i, 0]];
+            }
+        }
+        self.layer.d_activation = &previous_layer.layer * &derivative;
+    }
+
+    // forward prop
+    fn forward_prop(&mut self, previous_layer: &Layer) {
+        self.layer.forward_prop(previous_layer);
+        self.activate();
+    }
+
+    // backwards prop, the slopes get their gradient averaged over the batch like the weights
+    fn backward_prop(&mut self, previous_layer: &Layer, next_layer: &Layer) {
+        self.deactivate(previous_layer);
+        self.layer.backward_prop(next_layer);
+        let samples = previous_layer.layer.ncols() as f32;
+        let negative = self.layer.preactivation.map(|x| x.min(0.0));
+        self.d_slopes = (&previous_layer.layer * &negative)
+            .sum_axis(Axis(1))
+            .map(|x| x / samples)
+            .insert_axis(Axis(1));
+    }
+
+    // weights, biases and slopes
+    fn parameters(&mut self) -> Vec<Parameter<'_>> {
+        let mut parameters = self.layer.parameters();
+        parameters.push(Parameter {
+            value: &mut self.slopes,
+            gradient: &self.d_slopes,
+            state: &mut self.slopes_state,
+        });
+        parameters
+    }
+
+    fn layer(&self) -> &Layer {
+        &self.layer
+    }
+
+    fn layer_mut(&mut self) -> &mut Layer {
+        &mut self.layer
+    }
+
+    fn spec(&self) -> LayerSpec {
+        LayerSpec::PReLU {
+            input: self.layer.weights.ncols(),
+            nodes: self.layer.weights.nrows(),
+            initializer: self.layer.initializer,
+        }
+    }
+
+    // the slopes are saved along with the weights and biases
+    fn checkpoint(&self) -> LayerCheckpoint {
+        LayerCheckpoint {
+            spec: self.spec(),
+            weights: self.layer.weights.clone(),
+            biases: self.layer.biases.clone(),
+            buffers: vec![self.slopes.clone()],
+        }
+    }
+
+    fn load_checkpoint(&mut self, checkpoint: &LayerCheckpoint) {
+        self.layer.weights = checkpoint.weights.clone();
+        self.layer.biases = checkpoint.biases.clone();
+        if let [slopes] = checkpoint.buffers.as_slice() {
+            self.slopes = slopes.clone();
+        }
+    }
+
+    // the slopes have optimizer state of their own
+    fn optimizer_states(&self) -> Vec<OptimizerState> {
+        vec![
+            self.layer.weights_state.clone(),
+            self.layer.biases_state.clone(),
+            self.slopes_state.clone(),
+        ]
+    }
+
+    fn load_optimizer_states(&mut self, states: &[OptimizerState]) {
+        if let [weights, biases, slopes] = states {
+            self.layer.weights_state = weights.clone();
+            self.layer.biases_state = biases.clone();
+            self.slopes_state = slopes.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gradient_check::{check_layer, random};
+    use crate::sequential::Sequential;
+    use ndarray_rand::rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn prelu_gradients() {
+        let mut prelu =
+            PReLU::new(6, 4, Initializer::HeNormal, &mut StdRng::seed_from_u64(0)).unwrap();
+        prelu.layer.weights = random((4, 6), 1);
+        prelu.layer.biases = random((4, 1), 2);
+        prelu.slopes = random((4, 1), 3);
+        check_layer(&mut prelu, &random((6, 3), 4), 5);
+    }
+
+    #[test]
+    fn prelu_slope_gradients() {
+        let mut prelu =
+            PReLU::new(6, 4, Initializer::HeNormal, &mut StdRng::seed_from_u64(0)).unwrap();
+        prelu.layer.weights = random((4, 6), 6);
+        prelu.slopes = random((4, 1), 7);
+        let input = Layer::dummy_layer(random((6, 3), 8));
+        let upstream = random((4, 3), 9);
+        prelu.forward_prop(&input);
+        prelu.backward_prop(&Layer::dummy_layer(upstream.clone()), &input);
+        // objective is sum(upstream * output), the slope gradient is averaged over 3 samples
+        let step = 1e-3;
+        for i in 0..4 {
+            let original = prelu.slopes[[i, 0]];
+            prelu.slopes[[i, 0]] = original + step;
+            prelu.forward_prop(&input);
+            let plus = (&prelu.layer.layer * &upstream).sum();
+            prelu.slopes[[i, 0]] = original - step;
+            prelu.forward_prop(&input);
+            let minus = (&prelu.layer.layer * &upstream).sum();
+            prelu.slopes[[i, 0]] = original;
+            let numeric = (plus - minus) / (2.0 * step);
+            assert!((prelu.d_slopes[[i, 0]] * 3.0 - numeric).abs() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn gradient_norm_includes_the_slopes() {
+        let mut prelu =
+            PReLU::new(3, 2, Initializer::HeNormal, &mut StdRng::seed_from_u64(0)).unwrap();
+        prelu.layer.d_weights = Array2::from_elem((2, 3), 1.0);
+        prelu.layer.d_biases = Array2::from_elem((2, 1), 2.0);
+        prelu.d_slopes = Array2::from_elem((2, 1), 4.0);
+        let mut network = Sequential::new();
+        network.add(Box::new(prelu)).unwrap();
+        // 6 weights of 1, 2 biases of 2 and 2 slopes of 4
+        assert_eq!(network.gradient_norm(), (6.0f32 + 8.0 + 32.0).sqrt());
+    }
+}
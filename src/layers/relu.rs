@@ -1,7 +1,9 @@
+use super::initializer::Initializer;
 use super::layer::{ActivationLayer, Layer};
-use ndarray::{prelude::Array2, Array, Ix2};
-use ndarray_rand::{rand_distr::Normal, RandomExt};
-extern crate blas_src;
+use crate::checkpoint::LayerSpec;
+use crate::error::{Error, Result};
+use ndarray::prelude::Array2;
+use ndarray_rand::rand::Rng;
 // implementation of relu with option to make it leaky
 pub struct ReLU {
     pub layer: Layer,
@@ -10,24 +12,29 @@ pub struct ReLU {
 
 impl ReLU {
     // create new relu layer
-    pub fn new(
+    // initializer: how the weights are drawn, he normal suits this activation
+    pub fn new<R: Rng + ?Sized>(
         input: usize,
         nodes: usize,
-        samples: usize,
-        alpha: f32,
         relu_coefficient: f32,
-    ) -> ReLU {
-        // create a normal layer
-        let mut layer = Layer::new_layer(input, nodes, samples, alpha);
-        // init weights with he initialization
-        layer.weights = Array::<f32, Ix2>::random(
-            (nodes, input),
-            Normal::new(0.0f32, 2f32 / input as f32).unwrap(),
-        );
-        ReLU {
-            layer: layer,
-            relu_coefficient: relu_coefficient,
+        initializer: Initializer,
+        rng: &mut R,
+    ) -> Result<ReLU> {
+        if input == 0 || nodes == 0 {
+            return Err(Error::InvalidConfig(format!(
+                "a ReLU layer needs at least one input and one node, got {} inputs and {} nodes",
+                input, nodes
+            )));
         }
+        // create a normal layer
+        let mut layer = Layer::new_layer(input, nodes);
+        // draw the weights, the biases start at 0
+        layer.weights = initializer.initialize((nodes, input), input, nodes, rng)?;
+        layer.initializer = initializer;
+        Ok(ReLU {
+            layer,
+            relu_coefficient,
+        })
     }
 
     // calculate derivative of relu
@@ -45,34 +52,85 @@ impl ReLU {
         }
         deriv
     }
+}
+
+impl ActivationLayer for ReLU {
+    // activate using relu piecewise function
+    fn activate(&mut self) {
+        let relu_coefficient = self.relu_coefficient;
+        self.layer.layer =
+            self.layer
+                .preactivation
+                .map(|x| if *x < 0.0 { x * relu_coefficient } else { *x });
+    }
+    // calculate gradient of the layer
+    fn deactivate(&mut self, previous_layer: &Layer) {
+        self.layer.d_activation = &previous_layer.layer * &self.derivate();
+    }
+
     // forward prop
-    pub fn forward_prop(&mut self, previous_layer: &Layer) {
-        self.layer.forward_prop(&previous_layer);
+    fn forward_prop(&mut self, previous_layer: &Layer) {
+        self.layer.forward_prop(previous_layer);
         self.activate();
     }
 
     // backwards prop
-    pub fn backward_prop(&mut self, previous_layer: &Layer, next_layer: &Layer) {
-        self.deactivate(&previous_layer);
-        self.layer.backward_prop(&next_layer);
+    fn backward_prop(&mut self, previous_layer: &Layer, next_layer: &Layer) {
+        self.deactivate(previous_layer);
+        self.layer.backward_prop(next_layer);
     }
-}
 
-impl ActivationLayer for ReLU {
-    // activate using relu piecewise function
-    fn activate(&mut self) {
-        for ((i, j), item) in self.layer.preactivation.indexed_iter() {
-            if *item < 0.0 {
-                self.layer.layer[[i, j]] =
-                    &self.layer.preactivation[[i, j]] * &self.relu_coefficient;
-            } else {
-                self.layer.layer[[i, j]] = self.layer.preactivation[[i, j]];
-            }
+    fn layer(&self) -> &Layer {
+        &self.layer
+    }
+
+    fn layer_mut(&mut self) -> &mut Layer {
+        &mut self.layer
+    }
+
+    fn spec(&self) -> LayerSpec {
+        LayerSpec::ReLU {
+            input: self.layer.weights.ncols(),
+            nodes: self.layer.weights.nrows(),
+            relu_coefficient: self.relu_coefficient,
+            initializer: self.layer.initializer,
         }
     }
-    // calculate gradient of the layer
-    fn deactivate(&mut self, previous_layer: &Layer) {
-        self.layer.d_activation =
-            (&previous_layer.weights.t().dot(&previous_layer.d_activation)) * &self.derivate();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gradient_check::{check_layer, random};
+    use ndarray_rand::rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn relu_gradients() {
+        let mut relu = ReLU::new(
+            6,
+            4,
+            0.0,
+            Initializer::HeNormal,
+            &mut StdRng::seed_from_u64(0),
+        )
+        .unwrap();
+        relu.layer.weights = random((4, 6), 1);
+        relu.layer.biases = random((4, 1), 2);
+        check_layer(&mut relu, &random((6, 3), 3), 4);
+    }
+
+    #[test]
+    fn leaky_relu_gradients() {
+        let mut relu = ReLU::new(
+            6,
+            4,
+            0.3,
+            Initializer::HeNormal,
+            &mut StdRng::seed_from_u64(0),
+        )
+        .unwrap();
+        relu.layer.weights = random((4, 6), 5);
+        relu.layer.biases = random((4, 1), 6);
+        check_layer(&mut relu, &random((6, 3), 7), 8);
     }
 }
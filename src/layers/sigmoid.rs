@@ -0,0 +1,88 @@
+use super::initializer::Initializer;
+use super::layer::{ActivationLayer, Layer};
+use crate::checkpoint::LayerSpec;
+use crate::error::{Error, Result};
+use ndarray_rand::rand::Rng;
+// implementation of the logistic sigmoid
+pub struct Sigmoid {
+    pub layer: Layer,
+}
+
+impl Sigmoid {
+    // create new sigmoid layer
+    // initializer: how the weights are drawn, xavier normal suits this activation
+    pub fn new<R: Rng + ?Sized>(
+        input: usize,
+        nodes: usize,
+        initializer: Initializer,
+        rng: &mut R,
+    ) -> Result<Sigmoid> {
+        if input == 0 || nodes == 0 {
+            return Err(Error::InvalidConfig(format!(
+                "a Sigmoid layer needs at least one input and one node, got {} inputs and {} nodes",
+                input, nodes
+            )));
+        }
+        // create a normal layer
+        let mut layer = Layer::new_layer(input, nodes);
+        // draw the weights, the biases start at 0
+        layer.weights = initializer.initialize((nodes, input), input, nodes, rng)?;
+        layer.initializer = initializer;
+        Ok(Sigmoid { layer })
+    }
+}
+
+impl ActivationLayer for Sigmoid {
+    // activate using 1 / (1 + e^-x)
+    fn activate(&mut self) {
+        self.layer.layer = self.layer.preactivation.map(|x| 1.0 / (1.0 + (-x).exp()));
+    }
+    // calculate gradient of the layer, the derivative is s * (1 - s) for output s
+    fn deactivate(&mut self, previous_layer: &Layer) {
+        self.layer.d_activation = &previous_layer.layer * &self.layer.layer.map(|s| s * (1.0 - s));
+    }
+
+    // forward prop
+    fn forward_prop(&mut self, previous_layer: &Layer) {
+        self.layer.forward_prop(previous_layer);
+        self.activate();
+    }
+
+    // backwards prop
+    fn backward_prop(&mut self, previous_layer: &Layer, next_layer: &Layer) {
+        self.deactivate(previous_layer);
+        self.layer.backward_prop(next_layer);
+    }
+
+    fn layer(&self) -> &Layer {
+        &self.layer
+    }
+
+    fn layer_mut(&mut self) -> &mut Layer {
+        &mut self.layer
+    }
+
+    fn spec(&self) -> LayerSpec {
+        LayerSpec::Sigmoid {
+            input: self.layer.weights.ncols(),
+            nodes: self.layer.weights.nrows(),
+            initializer: self.layer.initializer,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gradient_check::{check_layer, random};
+    use ndarray_rand::rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn sigmoid_gradients() {
+        let mut sigmoid =
+            Sigmoid::new(6, 4, Initializer::HeNormal, &mut StdRng::seed_from_u64(0)).unwrap();
+        sigmoid.layer.weights = random((4, 6), 1);
+        sigmoid.layer.biases = random((4, 1), 2);
+        check_layer(&mut sigmoid, &random((6, 3), 3), 4);
+    }
+}
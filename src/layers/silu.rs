@@ -0,0 +1,92 @@
+use super::initializer::Initializer;
+use super::layer::{ActivationLayer, Layer};
+use crate::checkpoint::LayerSpec;
+use crate::error::{Error, Result};
+use ndarray_rand::rand::Rng;
+// implementation of the sigmoid linear unit, also known as swish, x * sigmoid(x)
+pub struct SiLU {
+    pub layer: Layer,
+}
+
+impl SiLU {
+    // create new silu layer
+    // initializer: how the weights are drawn, he normal suits this activation
+    pub fn new<R: Rng + ?Sized>(
+        input: usize,
+        nodes: usize,
+        initializer: Initializer,
+        rng: &mut R,
+    ) -> Result<SiLU> {
+        if input == 0 || nodes == 0 {
+            return Err(Error::InvalidConfig(format!(
+                "a SiLU layer needs at least one input and one node, got {} inputs and {} nodes",
+                input, nodes
+            )));
+        }
+        // create a normal layer
+        let mut layer = Layer::new_layer(input, nodes);
+        // draw the weights, the biases start at 0
+        layer.weights = initializer.initialize((nodes, input), input, nodes, rng)?;
+        layer.initializer = initializer;
+        Ok(SiLU { layer })
+    }
+}
+
+impl ActivationLayer for SiLU {
+    // activate using x / (1 + e^-x)
+    fn activate(&mut self) {
+        self.layer.layer = self.layer.preactivation.map(|x| x / (1.0 + (-x).exp()));
+    }
+    // calculate gradient of the layer, the derivative is s * (1 + x * (1 - s)) for s = sigmoid(x)
+    fn deactivate(&mut self, previous_layer: &Layer) {
+        let derivative = self.layer.preactivation.map(|x| {
+            let s = 1.0 / (1.0 + (-x).exp());
+            s * (1.0 + x * (1.0 - s))
+        });
+        self.layer.d_activation = &previous_layer.layer * &derivative;
+    }
+
+    // forward prop
+    fn forward_prop(&mut self, previous_layer: &Layer) {
+        self.layer.forward_prop(previous_layer);
+        self.activate();
+    }
+
+    // backwards prop
+    fn backward_prop(&mut self, previous_layer: &Layer, next_layer: &Layer) {
+        self.deactivate(previous_layer);
+        self.layer.backward_prop(next_layer);
+    }
+
+    fn layer(&self) -> &Layer {
+        &self.layer
+    }
+
+    fn layer_mut(&mut self) -> &mut Layer {
+        &mut self.layer
+    }
+
+    fn spec(&self) -> LayerSpec {
+        LayerSpec::SiLU {
+            input: self.layer.weights.ncols(),
+            nodes: self.layer.weights.nrows(),
+            initializer: self.layer.initializer,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gradient_check::{check_layer, random};
+    use ndarray_rand::rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn silu_gradients() {
+        let mut silu =
+            SiLU::new(6, 4, Initializer::HeNormal, &mut StdRng::seed_from_u64(0)).unwrap();
+        silu.layer.weights = random((4, 6), 1);
+        silu.layer.biases = random((4, 1), 2);
+        check_layer(&mut silu, &random((6, 3), 3), 4);
+    }
+}
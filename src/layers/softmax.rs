@@ -1,8 +1,19 @@
-use ndarray::{prelude::Array2, Array, Ix2};
-use ndarray_rand::{rand_distr::Normal, RandomExt};
+use ndarray::{prelude::Array2, Axis};
+use ndarray_rand::rand::Rng;
 
+use super::initializer::Initializer;
 use super::layer::{ActivationLayer, Layer};
-extern crate blas_src;
+use crate::checkpoint::LayerSpec;
+use crate::error::{Error, Result};
+
+// jacobian of softmax applied to a gradient with respect to its output: y * (g - sum(g * y)) for
+// each sample
+// output: softmax output y, one column per sample
+pub fn jacobian_product(output: &Array2<f32>, gradient: &Array2<f32>) -> Array2<f32> {
+    let weighted = gradient * output;
+    let sums = weighted.sum_axis(Axis(0)).insert_axis(Axis(0));
+    &weighted - &(output * &sums)
+}
 
 // implementation of softmax layer
 pub struct Softmax {
@@ -10,25 +21,25 @@ pub struct Softmax {
 }
 
 impl Softmax {
-    pub fn new(input: usize, nodes: usize, samples: usize, alpha: f32) -> Softmax {
+    // initializer: how the weights are drawn, xavier normal suits this activation
+    pub fn new<R: Rng + ?Sized>(
+        input: usize,
+        nodes: usize,
+        initializer: Initializer,
+        rng: &mut R,
+    ) -> Result<Softmax> {
+        if input == 0 || nodes == 0 {
+            return Err(Error::InvalidConfig(format!(
+                "a Softmax layer needs at least one input and one node, got {} inputs and {} nodes",
+                input, nodes
+            )));
+        }
         // normal layer init
-        let mut layer = Layer::new_layer(input, nodes, samples, alpha);
-        // xavier init for softmax
-        layer.weights =
-            Array::<f32, Ix2>::random((nodes, input), Normal::new(0.0f32, 1.0f32).unwrap());
-        Softmax { layer: layer }
-    }
-
-    // normal forward prop
-    pub fn forward_prop(&mut self, previous_layer: &Layer) {
-        self.layer.forward_prop(&previous_layer);
-        self.activate();
-    }
-
-    // backwards prop function
-    pub fn backward_prop(&mut self, previous_layer: &Layer, next_layer: &Layer) {
-        self.deactivate(&Layer::one_hot(&previous_layer.layer));
-        self.layer.backward_prop(&next_layer);
+        let mut layer = Layer::new_layer(input, nodes);
+        // draw the weights, the biases start at 0
+        layer.weights = initializer.initialize((nodes, input), input, nodes, rng)?;
+        layer.initializer = initializer;
+        Ok(Softmax { layer })
     }
 }
 
@@ -37,16 +48,17 @@ impl ActivationLayer for Softmax {
     fn activate(&mut self) {
         let mut row_sums = Array2::<f32>::zeros((1, self.layer.preactivation.ncols()));
         let mut out = Array2::<f32>::zeros(self.layer.preactivation.raw_dim());
-        let mut m = f32::NEG_INFINITY;
-        // get max in layer
-        for item in self.layer.preactivation.iter() {
-            if *item > m {
-                m = *item;
+        // get max of each column, so that no sample underflows to all zeros
+        let mut m =
+            Array2::<f32>::from_elem((1, self.layer.preactivation.ncols()), f32::NEG_INFINITY);
+        for ((_, j), item) in self.layer.preactivation.indexed_iter() {
+            if *item > m[[0, j]] {
+                m[[0, j]] = *item;
             }
         }
         // subtract max from each element, and add it to sum of its row
         for ((i, j), item) in self.layer.preactivation.indexed_iter() {
-            out[[i, j]] = (*item - m).exp();
+            out[[i, j]] = (*item - m[[0, j]]).exp();
             row_sums[[0, j]] += out[[i, j]];
         }
         // divide each element by sum of its row
@@ -56,8 +68,64 @@ impl ActivationLayer for Softmax {
         self.layer.layer = out;
     }
 
-    // calculate derivative of activation
+    // calculate derivative of activation, previous_layer holds the gradient of the loss with
+    // respect to the softmax output
     fn deactivate(&mut self, previous_layer: &Layer) {
-        self.layer.d_activation = (&self.layer.layer - &previous_layer.layer).map(|x| x * 2f32);
+        self.layer.d_activation = jacobian_product(&self.layer.layer, &previous_layer.layer);
+    }
+
+    // normal forward prop
+    fn forward_prop(&mut self, previous_layer: &Layer) {
+        self.layer.forward_prop(previous_layer);
+        self.activate();
+    }
+
+    // backwards prop function
+    fn backward_prop(&mut self, previous_layer: &Layer, next_layer: &Layer) {
+        self.deactivate(previous_layer);
+        self.layer.backward_prop(next_layer);
+    }
+
+    fn layer(&self) -> &Layer {
+        &self.layer
+    }
+
+    fn layer_mut(&mut self) -> &mut Layer {
+        &mut self.layer
+    }
+
+    fn spec(&self) -> LayerSpec {
+        LayerSpec::Softmax {
+            input: self.layer.weights.ncols(),
+            nodes: self.layer.weights.nrows(),
+            initializer: self.layer.initializer,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gradient_check::{check_layer, random};
+    use ndarray_rand::rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn softmax_gradients() {
+        let mut softmax =
+            Softmax::new(6, 5, Initializer::HeNormal, &mut StdRng::seed_from_u64(0)).unwrap();
+        softmax.layer.weights = random((5, 6), 1);
+        softmax.layer.biases = random((5, 1), 2);
+        check_layer(&mut softmax, &random((6, 3), 3), 4);
+    }
+
+    #[test]
+    fn softmax_columns_sum_to_one() {
+        let mut softmax =
+            Softmax::new(6, 5, Initializer::HeNormal, &mut StdRng::seed_from_u64(0)).unwrap();
+        // large inputs would overflow exp without subtracting the max of each column
+        softmax.forward_prop(&Layer::dummy_layer(random((6, 3), 5) * 1000f32));
+        for sum in softmax.layer.layer.sum_axis(Axis(0)) {
+            assert!((sum - 1f32).abs() < 1e-5);
+        }
     }
 }
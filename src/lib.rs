@@ -0,0 +1,55 @@
+// rustnist: a small neural network library for classifying mnist style images
+// build a Model from Hyperparameters and a data directory, or a Sequential network by hand from
+// layers, then train, evaluate, save and load it, or predict with predict::predict_images
+
+// link the BLAS backend picked with cargo features, ndarray finds it through the blas symbols
+#[cfg(feature = "blas")]
+extern crate blas_src;
+
+pub mod augmentation;
+pub mod checkpoint;
+pub mod error;
+#[cfg(test)]
+mod gradient_check;
+pub mod image;
+pub mod layers;
+pub mod logger;
+pub mod loss;
+pub mod metrics;
+pub mod model;
+pub mod optimizer;
+pub mod predict;
+pub mod scheduler;
+pub mod sequential;
+pub mod sweep;
+
+pub use augmentation::Augmentation;
+pub use checkpoint::{Checkpoint, LayerCheckpoint, LayerSpec, TrainingState};
+pub use error::{Error, Result};
+pub use layers::{
+    activation::Activation,
+    batchnorm::BatchNorm,
+    conv2d::Conv2d,
+    dataset::{Dataset, DatasetKind},
+    dropout::Dropout,
+    elu::ELU,
+    gelu::GELU,
+    initializer::Initializer,
+    layer::{ActivationLayer, Layer, Parameter},
+    maxpool2d::MaxPool2d,
+    prelu::PReLU,
+    relu::ReLU,
+    sigmoid::Sigmoid,
+    silu::SiLU,
+    softmax::Softmax,
+    tanh::Tanh,
+};
+pub use logger::{LogEntry, LogFormat, LogKind, TrainingLog};
+pub use loss::Loss;
+pub use metrics::{Metrics, ReportFormat};
+pub use model::{Hyperparameters, Model, TrainingRun, CONFIG};
+pub use optimizer::{Optimizer, OptimizerKind};
+pub use predict::Prediction;
+pub use scheduler::{Scheduler, SchedulerKind};
+pub use sequential::Sequential;
+pub use sweep::{Search, Sweep, SweepSpec, Trial, TrialResult};
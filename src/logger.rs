@@ -0,0 +1,212 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+
+// columns of a csv training log, in the order of the fields of LogEntry
+const CSV_HEADER: &str = "kind,epoch,step,loss,accuracy,validation_loss,validation_accuracy,\
+learning_rate,gradient_norm,time";
+
+// file formats a training log can be written in
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    // comma separated values with a header row
+    Csv,
+    // one json object per line
+    Jsonl,
+}
+
+impl LogFormat {
+    // pick the format from a file extension, defaulting to csv
+    pub fn from_path(path: &Path) -> LogFormat {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension)
+                if extension.eq_ignore_ascii_case("jsonl")
+                    || extension.eq_ignore_ascii_case("json") =>
+            {
+                LogFormat::Jsonl
+            }
+            _ => LogFormat::Csv,
+        }
+    }
+}
+
+// whether a row of the log covers a single batch or a whole epoch
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogKind {
+    Step,
+    Epoch,
+}
+
+// one row of a training log
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct LogEntry {
+    pub kind: LogKind,
+    // epochs finished when the row was written, so the first epoch is logged as 1 and its
+    // batches as 0
+    pub epoch: usize,
+    // updates made so far
+    pub step: usize,
+    // mean training loss and accuracy over the batch or epoch
+    pub loss: f32,
+    pub accuracy: f32,
+    // validation loss and accuracy, only for epochs the validation set was evaluated after
+    pub validation_loss: Option<f32>,
+    pub validation_accuracy: Option<f32>,
+    // learning rate of the last update
+    pub learning_rate: f32,
+    // l2 norm of the gradients of every learned parameter, averaged over the samples of an epoch
+    pub gradient_norm: f32,
+    // seconds of training so far, including the time before the run was resumed
+    pub time: f64,
+}
+
+impl LogEntry {
+    // row of a csv log, leaving missing validation results empty
+    fn to_csv(&self) -> String {
+        let optional =
+            |value: Option<f32>| value.map(|value| value.to_string()).unwrap_or_default();
+        format!(
+            "{},{},{},{},{},{},{},{},{},{}",
+            match self.kind {
+                LogKind::Step => "step",
+                LogKind::Epoch => "epoch",
+            },
+            self.epoch,
+            self.step,
+            self.loss,
+            self.accuracy,
+            optional(self.validation_loss),
+            optional(self.validation_accuracy),
+            self.learning_rate,
+            self.gradient_norm,
+            self.time
+        )
+    }
+}
+
+// file a row is written to for every epoch of training, and for every batch when steps is set
+pub struct TrainingLog {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    format: LogFormat,
+    // also write a row for every batch
+    pub steps: bool,
+}
+
+impl TrainingLog {
+    // start a new log, replacing the file if it exists
+    pub fn create(path: &Path, format: LogFormat, steps: bool) -> Result<TrainingLog> {
+        TrainingLog::open(path, format, steps, false)
+    }
+
+    // continue the log of a resumed run at the end of its file
+    pub fn append(path: &Path, format: LogFormat, steps: bool) -> Result<TrainingLog> {
+        TrainingLog::open(path, format, steps, true)
+    }
+
+    fn open(path: &Path, format: LogFormat, steps: bool, append: bool) -> Result<TrainingLog> {
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)
+            .map_err(|err| Error::io(path, err))?;
+        let empty = file.metadata().map_err(|err| Error::io(path, err))?.len() == 0;
+        let mut log = TrainingLog {
+            path: path.to_path_buf(),
+            writer: BufWriter::new(file),
+            format,
+            steps,
+        };
+        // a log that is appended to already has its header
+        if format == LogFormat::Csv && empty {
+            writeln!(log.writer, "{}", CSV_HEADER).map_err(|err| Error::io(path, err))?;
+        }
+        Ok(log)
+    }
+
+    // add a row to the log, epochs are flushed to the file right away so it can be followed
+    // while training
+    pub fn write(&mut self, entry: &LogEntry) -> Result<()> {
+        let line = match self.format {
+            LogFormat::Csv => entry.to_csv(),
+            LogFormat::Jsonl => serde_json::to_string(entry).unwrap(),
+        };
+        writeln!(self.writer, "{}", line).map_err(|err| Error::io(&self.path, err))?;
+        if entry.kind == LogKind::Epoch {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    // write out rows that are still buffered
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer
+            .flush()
+            .map_err(|err| Error::io(&self.path, err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn entry(kind: LogKind, validation: Option<f32>) -> LogEntry {
+        LogEntry {
+            kind,
+            epoch: 1,
+            step: 4,
+            loss: 0.5,
+            accuracy: 0.75,
+            validation_loss: validation,
+            validation_accuracy: validation,
+            learning_rate: 0.01,
+            gradient_norm: 2.0,
+            time: 1.5,
+        }
+    }
+
+    #[test]
+    fn csv_log_appends_without_repeating_the_header() {
+        let path = std::env::temp_dir().join(format!("rustnist-log-{}.csv", std::process::id()));
+        let mut log = TrainingLog::create(&path, LogFormat::Csv, true).unwrap();
+        log.write(&entry(LogKind::Step, None)).unwrap();
+        log.write(&entry(LogKind::Epoch, Some(0.25))).unwrap();
+        drop(log);
+        let mut log = TrainingLog::append(&path, LogFormat::Csv, false).unwrap();
+        log.write(&entry(LogKind::Epoch, None)).unwrap();
+        drop(log);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!(
+                "{}\nstep,1,4,0.5,0.75,,,0.01,2,1.5\nepoch,1,4,0.5,0.75,0.25,0.25,0.01,2,1.5\n\
+                 epoch,1,4,0.5,0.75,,,0.01,2,1.5\n",
+                CSV_HEADER
+            )
+        );
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn jsonl_log_writes_an_object_per_line() {
+        let path = std::env::temp_dir().join(format!("rustnist-log-{}.jsonl", std::process::id()));
+        assert_eq!(LogFormat::from_path(&path), LogFormat::Jsonl);
+        let mut log = TrainingLog::create(&path, LogFormat::Jsonl, false).unwrap();
+        log.write(&entry(LogKind::Epoch, None)).unwrap();
+        drop(log);
+        let contents = fs::read_to_string(&path).unwrap();
+        let row: serde_json::Value = serde_json::from_str(contents.trim_end()).unwrap();
+        assert_eq!(row["kind"], "epoch");
+        assert_eq!(row["validation_loss"], serde_json::Value::Null);
+        assert_eq!(row["gradient_norm"], 2.0);
+        fs::remove_file(path).unwrap();
+    }
+}
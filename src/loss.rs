@@ -0,0 +1,144 @@
+use clap::ValueEnum;
+use ndarray::{prelude::Array2, Zip};
+use serde::{Deserialize, Serialize};
+
+use crate::layers::softmax::jacobian_product;
+
+// smallest probability used inside logarithms and divisions, keeps both finite for outputs that
+// underflowed to zero
+const EPSILON: f32 = f32::MIN_POSITIVE;
+
+// loss functions for comparing the output of the network to one hot encoded labels
+// the loss value is averaged over the samples of a batch, while the gradient is kept per sample
+// since each layer already divides its parameter gradients by the amount of samples
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Loss {
+    // -sum(t * ln(y)) over every class
+    #[default]
+    #[clap(name = "cross-entropy")]
+    CrossEntropy,
+    // sum((y - t)^2) over every class
+    #[clap(name = "mse")]
+    MeanSquaredError,
+}
+
+impl Loss {
+    // mean loss of a batch
+    // output: network output, one column per sample
+    // targets: one hot encoded labels in the same shape as output
+    pub fn loss(&self, output: &Array2<f32>, targets: &Array2<f32>) -> f32 {
+        let samples = output.ncols() as f32;
+        let total = match self {
+            Loss::CrossEntropy => Zip::from(output)
+                .and(targets)
+                .fold(0f32, |sum, y, t| sum - t * y.max(EPSILON).ln()),
+            Loss::MeanSquaredError => Zip::from(output)
+                .and(targets)
+                .fold(0f32, |sum, y, t| sum + (y - t) * (y - t)),
+        };
+        total / samples
+    }
+
+    // gradient of the loss of each sample with respect to the network output
+    pub fn gradient(&self, output: &Array2<f32>, targets: &Array2<f32>) -> Array2<f32> {
+        match self {
+            Loss::CrossEntropy => Zip::from(output)
+                .and(targets)
+                .map_collect(|y, t| -t / y.max(EPSILON)),
+            Loss::MeanSquaredError => Zip::from(output)
+                .and(targets)
+                .map_collect(|y, t| 2f32 * (y - t)),
+        }
+    }
+
+    // gradient of the loss of each sample with respect to the preactivation of a softmax output
+    // layer
+    // cross entropy is fused with the softmax into y - t, which stays exact when the probability
+    // of the labelled class underflows, where going through the jacobian would lose it entirely
+    pub fn softmax_gradient(&self, output: &Array2<f32>, targets: &Array2<f32>) -> Array2<f32> {
+        match self {
+            Loss::CrossEntropy => output - targets,
+            Loss::MeanSquaredError => jacobian_product(output, &self.gradient(output, targets)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gradient_check::{check_loss, random};
+    use crate::layers::layer::Layer;
+
+    // positive outputs like those of a softmax layer, with one hot targets for 10 classes
+    fn output_and_targets() -> (Array2<f32>, Array2<f32>) {
+        let output = random((10, 4), 1).map(|x| (x + 1.5) / 10f32);
+        let labels = Array2::from_shape_vec((1, 4), vec![3f32, 0f32, 9f32, 3f32]).unwrap();
+        (output, Layer::one_hot(&labels, 10).layer)
+    }
+
+    #[test]
+    fn cross_entropy_gradient() {
+        let (output, targets) = output_and_targets();
+        check_loss(Loss::CrossEntropy, &output, &targets);
+    }
+
+    #[test]
+    fn mean_squared_error_gradient() {
+        let (output, targets) = output_and_targets();
+        check_loss(Loss::MeanSquaredError, &output, &targets);
+    }
+
+    fn softmax(preactivation: &Array2<f32>) -> Array2<f32> {
+        let mut output = preactivation.map(|x| x.exp());
+        for mut column in output.columns_mut() {
+            let sum = column.sum();
+            column.map_inplace(|x| *x /= sum);
+        }
+        output
+    }
+
+    // compare the gradient with respect to a softmax preactivation to central differences of the
+    // loss of the softmax output
+    fn check_softmax_gradient(loss: Loss) {
+        let (_, targets) = output_and_targets();
+        let preactivation = random((10, 4), 2);
+        let samples = preactivation.ncols() as f32;
+        let gradient = loss.softmax_gradient(&softmax(&preactivation), &targets);
+        let step = 1e-2;
+        let mut perturbed = preactivation.clone();
+        for index in ndarray::indices(preactivation.dim()) {
+            perturbed[index] = preactivation[index] + step;
+            let plus = loss.loss(&softmax(&perturbed), &targets);
+            perturbed[index] = preactivation[index] - step;
+            let minus = loss.loss(&softmax(&perturbed), &targets);
+            perturbed[index] = preactivation[index];
+            let numeric = samples * (plus - minus) / (2f32 * step);
+            assert!(
+                (gradient[index] - numeric).abs() < 1e-2,
+                "gradient at {:?} is {}, but the numeric gradient is {}",
+                index,
+                gradient[index],
+                numeric
+            );
+        }
+    }
+
+    #[test]
+    fn cross_entropy_softmax_gradient() {
+        check_softmax_gradient(Loss::CrossEntropy);
+    }
+
+    #[test]
+    fn mean_squared_error_softmax_gradient() {
+        check_softmax_gradient(Loss::MeanSquaredError);
+    }
+
+    #[test]
+    fn cross_entropy_softmax_gradient_survives_underflow() {
+        // the labelled class has a probability that underflowed to zero
+        let output = Array2::from_shape_vec((3, 1), vec![1f32, 0f32, 0f32]).unwrap();
+        let targets = Array2::from_shape_vec((3, 1), vec![0f32, 1f32, 0f32]).unwrap();
+        let gradient = Loss::CrossEntropy.softmax_gradient(&output, &targets);
+        assert_eq!(gradient.into_raw_vec(), vec![1f32, -1f32, 0f32]);
+    }
+}
@@ -1,30 +1,418 @@
-mod layers;
-mod model;
-use clap::Parser;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::Ordering;
 
-use crate::model::Model;
+use clap::{Parser, Subcommand};
+
+use rustnist::predict::{self, load_network};
+use rustnist::{
+    Activation, Augmentation, DatasetKind, Error, Hyperparameters, Initializer, LogFormat, Loss,
+    Model, OptimizerKind, ReportFormat, Result, SchedulerKind, Search, Sweep, SweepSpec,
+    TrainingLog,
+};
+
+// epochs trained for when --epochs is not given, except for a model loaded with --load
+const DEFAULT_EPOCHS: usize = 1000;
 
 // command-line parsing for hyperparameters
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
-    /// # of nodes in the hidden layer
-    #[clap(short, long, value_parser, default_value_t = 128)]
-    layer_size: usize,
+    /// # of nodes in each hidden layer, comma separated (e.g. 256,128,64)
+    #[clap(
+        short,
+        long,
+        value_parser,
+        value_delimiter = ',',
+        default_value = "128"
+    )]
+    layers: Vec<usize>,
+    /// Activation function of the hidden layers, each with its own weight initialization
+    #[clap(long, value_enum, default_value_t = Activation::ReLU)]
+    activation: Activation,
+    /// Initialization of the weights of each convolutional, hidden and output layer in order,
+    /// comma separated, or a single one for every convolutional and hidden layer, picked to suit
+    /// each layer when not given: he-normal, he-uniform, xavier-normal, xavier-uniform,
+    /// lecun-normal, lecun-uniform, orthogonal, zeros, normal:STD or uniform:LIMIT
+    #[clap(long, value_parser, value_delimiter = ',')]
+    init: Vec<Initializer>,
+    /// Seed of the random number generator behind initialization, shuffling, dropout and
+    /// augmentation, picked at random when not given and saved in checkpoints
+    #[clap(long, value_parser)]
+    seed: Option<u64>,
+    /// Normalize the output of every convolutional and hidden layer with batch normalization
+    #[clap(long, value_parser)]
+    batch_norm: bool,
+    /// Probability of dropping each node of the hidden layers while training, 0 disables dropout
+    #[clap(long, value_parser, default_value_t = 0.0)]
+    dropout: f32,
+    /// # of filters in each convolutional layer before the hidden layers, comma separated, each
+    /// followed by 2x2 max pooling (e.g. 6,16 for a LeNet-style network)
+    #[clap(short, long, value_parser, value_delimiter = ',')]
+    conv: Vec<usize>,
+    /// Width and height of the convolutional filters
+    #[clap(short, long, value_parser, default_value_t = 5)]
+    kernel_size: usize,
     /// Learning rate of the network
     #[clap(short, long, value_parser, default_value_t = 0.01)]
     alpha: f32,
     /// Batch size for BGD
     #[clap(short, long, value_parser, default_value_t = 100)]
     batch_size: isize,
-    /// Amount of epochs to train for
-    #[clap(short, long, value_parser, default_value_t = 1000)]
-    epochs: usize,
+    /// Amount of epochs to train for [default: 1000, or none with --load]
+    #[clap(short, long, value_parser)]
+    epochs: Option<usize>,
+    /// Loss function minimized during training
+    #[clap(long, value_enum, default_value_t = Loss::CrossEntropy)]
+    loss: Loss,
+    /// Update rule for weights and biases
+    #[clap(long, value_enum, default_value_t = OptimizerKind::Sgd)]
+    optimizer: OptimizerKind,
+    /// Momentum coefficient, also used as beta1 for adam and adamw
+    #[clap(long, value_parser, default_value_t = 0.9)]
+    momentum: f32,
+    /// Decay of the running average of squared gradients for rmsprop, adam and adamw
+    #[clap(long, value_parser, default_value_t = 0.999)]
+    beta2: f32,
+    /// L2 penalty on weights and biases, decoupled from the gradient for adamw
+    #[clap(long, value_parser, default_value_t = 0.0)]
+    weight_decay: f32,
+    /// Schedule the learning rate follows, starting from alpha
+    #[clap(long, value_enum, default_value_t = SchedulerKind::Constant)]
+    scheduler: SchedulerKind,
+    /// Epochs between decays for the step schedule, and length of each cosine cycle
+    #[clap(long, value_parser, default_value_t = 10)]
+    step_size: usize,
+    /// Decay factor of the step, exponential and plateau schedules
+    #[clap(long, value_parser, default_value_t = 0.1)]
+    gamma: f32,
+    /// Lowest learning rate the schedules decay to
+    #[clap(long, value_parser, default_value_t = 0.0)]
+    min_alpha: f32,
+    /// Epochs the learning rate is linearly increased over at the start of training
+    #[clap(long, value_parser, default_value_t = 0)]
+    warmup: usize,
+    /// Validations without a lower validation loss before the plateau schedule decays
+    #[clap(long, value_parser, default_value_t = 2)]
+    plateau_patience: usize,
+    /// Fraction of the training set held out for validation, taken from its end
+    #[clap(long, value_parser, default_value_t = 0.0)]
+    validation_split: f32,
+    /// Amount of epochs between evaluations on the validation set
+    #[clap(long, value_parser, default_value_t = 1)]
+    validate_every: usize,
+    /// Stop after this many validations without a lower validation loss and restore the best
+    /// weights, 0 disables early stopping
+    #[clap(long, value_parser, default_value_t = 0)]
+    patience: usize,
+    /// Largest random shift of training images in pixels, along each axis
+    #[clap(long, value_parser, default_value_t = 0.0)]
+    shift: f32,
+    /// Largest random rotation of training images in degrees, either way
+    #[clap(long, value_parser, default_value_t = 0.0)]
+    rotation: f32,
+    /// Largest random relative change in size of training images (e.g. 0.1 for 90% to 110%)
+    #[clap(long, value_parser, default_value_t = 0.0)]
+    scale: f32,
+    /// Largest displacement in pixels of the random elastic distortion of training images
+    #[clap(long, value_parser, default_value_t = 0.0)]
+    elastic_alpha: f32,
+    /// Smoothness of the elastic distortion, as the std of its gaussian blur in pixels
+    #[clap(long, value_parser, default_value_t = 4.0)]
+    elastic_sigma: f32,
+    /// Std of the gaussian noise added to every pixel of training images
+    #[clap(long, value_parser, default_value_t = 0.0)]
+    noise: f32,
+    /// Probability of erasing a random rectangle of each training image
+    #[clap(long, value_parser, default_value_t = 0.0)]
+    erase: f32,
+    /// Dataset to train and test on, read from the idx files in the data directory
+    #[clap(long, value_enum, default_value_t = DatasetKind::Mnist)]
+    dataset: DatasetKind,
+    /// Directory holding the idx files of the dataset, optionally gzipped
+    #[clap(long, value_parser, default_value = "data")]
+    data_dir: PathBuf,
+    /// Save the trained model to a checkpoint file
+    #[clap(long, value_parser)]
+    save: Option<PathBuf>,
+    /// Load a model from a checkpoint file instead of creating a new one, its saved
+    /// architecture and hyperparameters take precedence over the flags above
+    #[clap(long, value_parser)]
+    load: Option<PathBuf>,
+    /// Continue the training run saved to a checkpoint file exactly where it stopped, up to the
+    /// epoch count it was started with, ignoring the flags above
+    #[clap(long, value_parser, conflicts_with = "load")]
+    resume: Option<PathBuf>,
+    /// File the training run is saved to periodically and on ctrl-c, for continuing it with
+    /// --resume, defaults to the resumed file or else the --save file
+    #[clap(long, value_parser)]
+    checkpoint: Option<PathBuf>,
+    /// Amount of epochs between saves of the training run, 0 only saves on ctrl-c
+    #[clap(long, value_parser, default_value_t = 10)]
+    checkpoint_every: usize,
+    /// Write the per class metrics of the test set to a file
+    #[clap(long, value_parser)]
+    report: Option<PathBuf>,
+    /// Format of the report, picked from its extension when not given
+    #[clap(long, value_enum)]
+    report_format: Option<ReportFormat>,
+    /// Write the loss, accuracy, validation results, learning rate, gradient norm and wall time
+    /// of every epoch to a file, appended to when resuming
+    #[clap(long, value_parser)]
+    log: Option<PathBuf>,
+    /// Format of the log, jsonl for .jsonl and .json files and csv otherwise when not given
+    #[clap(long, value_enum)]
+    log_format: Option<LogFormat>,
+    /// Also write a row for every batch to the log
+    #[clap(long, value_parser)]
+    log_steps: bool,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+// subcommands run instead of training
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Predict the class of each image with a saved model
+    Predict {
+        /// Checkpoint file of the model to predict with
+        #[clap(short, long, value_parser)]
+        model: PathBuf,
+        /// Grayscale PNG or PGM images of any size
+        #[clap(value_parser, required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Train a model for every configuration of a grid or random search in parallel and rank
+    /// them by validation accuracy, the flags before `sweep` give the hyperparameters that are
+    /// not swept
+    Sweep(SweepArgs),
+}
+
+// values to sweep, each list left empty keeps the value of the flags before `sweep`
+#[derive(clap::Args, Debug)]
+struct SweepArgs {
+    /// Json file with the values to try (e.g. {"search": "random", "trials": 5, "alpha": [0.1,
+    /// 0.01]}), the flags below override its fields
+    #[clap(long, value_parser)]
+    spec: Option<PathBuf>,
+    /// Train every combination of the values, or a random selection of them [default: grid]
+    #[clap(long, value_enum)]
+    search: Option<Search>,
+    /// Amount of combinations random search trains [default: 10]
+    #[clap(long, value_parser)]
+    trials: Option<usize>,
+    /// Hidden layer sizes to try, the flag is repeated for each (e.g. --layers 128 --layers
+    /// 256,128)
+    #[clap(long, value_parser = parse_sizes)]
+    layers: Vec<Vec<usize>>,
+    /// Learning rates to try, comma separated
+    #[clap(long, value_parser, value_delimiter = ',')]
+    alpha: Vec<f32>,
+    /// Batch sizes to try, comma separated
+    #[clap(long, value_parser, value_delimiter = ',')]
+    batch_size: Vec<isize>,
+    /// Amounts of epochs to try, comma separated
+    #[clap(long, value_parser, value_delimiter = ',')]
+    epochs: Vec<usize>,
+    /// Amount of configurations trained at once, 0 trains one per cpu
+    #[clap(long, value_parser, default_value_t = 0)]
+    jobs: usize,
+    /// Write the ranked results to a file, as csv or json picked from its extension
+    #[clap(long, value_parser)]
+    output: Option<PathBuf>,
+}
+
+// comma separated layer sizes
+fn parse_sizes(value: &str) -> std::result::Result<Vec<usize>, String> {
+    value
+        .split(',')
+        .map(|size| {
+            size.trim()
+                .parse()
+                .map_err(|err| format!("{}: {}", size, err))
+        })
+        .collect()
+}
+
+// print the name of the predicted class and the softmax probabilities for each image
+fn run_predict(model: &Path, files: &[PathBuf]) -> Result<()> {
+    let (mut network, dataset) = load_network(model)?;
+    for (file, prediction) in
+        files
+            .iter()
+            .zip(predict::predict_images(&mut network, dataset, files)?)
+    {
+        let formatted: Vec<String> = prediction
+            .probabilities
+            .iter()
+            .map(|probability| format!("{:.4}", probability))
+            .collect();
+        println!(
+            "{}: {} [{}]",
+            file.display(),
+            dataset.label(prediction.class),
+            formatted.join(", ")
+        );
+    }
+    Ok(())
+}
+
+// hyperparameters of a new model, taken from the command line
+fn hyperparameters(args: &Args) -> Hyperparameters {
+    Hyperparameters {
+        dataset: args.dataset,
+        conv: args.conv.clone(),
+        kernel_size: args.kernel_size,
+        layers: args.layers.clone(),
+        activation: args.activation,
+        seed: args.seed,
+        initializers: args.init.clone(),
+        batch_norm: args.batch_norm,
+        dropout: args.dropout,
+        alpha: args.alpha,
+        batch_size: args.batch_size,
+        loss: args.loss,
+        optimizer: args.optimizer,
+        momentum: args.momentum,
+        beta2: args.beta2,
+        weight_decay: args.weight_decay,
+        scheduler: args.scheduler,
+        step_size: args.step_size,
+        gamma: args.gamma,
+        min_alpha: args.min_alpha,
+        warmup: args.warmup,
+        plateau_patience: args.plateau_patience,
+        validation_split: args.validation_split,
+        validate_every: args.validate_every,
+        patience: args.patience,
+        augmentation: Augmentation {
+            shift: args.shift,
+            rotation: args.rotation,
+            scale: args.scale,
+            elastic_alpha: args.elastic_alpha,
+            elastic_sigma: args.elastic_sigma,
+            noise: args.noise,
+            erase: args.erase,
+        },
+    }
+}
+
+// create, load or resume a model, train and test it, then write the report and checkpoint
+fn run_training(args: Args) -> Result<()> {
+    let mut model = match args.load.as_ref().or(args.resume.as_ref()) {
+        Some(path) => Model::load(path, &args.data_dir)?,
+        None => Model::new(hyperparameters(&args), &args.data_dir)?,
+    };
+    // printed so any run can be repeated with --seed
+    println!("Seed: {}", model.seed());
+    if let Some(path) = &args.log {
+        let format = args
+            .log_format
+            .unwrap_or_else(|| LogFormat::from_path(path));
+        model.log_to(if args.resume.is_some() {
+            TrainingLog::append(path, format, args.log_steps)?
+        } else {
+            TrainingLog::create(path, format, args.log_steps)?
+        });
+    }
+    let checkpoint = args
+        .checkpoint
+        .as_ref()
+        .or(args.resume.as_ref())
+        .or(args.save.as_ref());
+    if let Some(path) = checkpoint {
+        model.autosave(path.clone(), args.checkpoint_every);
+        // ctrl-c saves and stops after the current batch, pressing it again stops straight away
+        // without a file to save to, ctrl-c keeps stopping the program straight away
+        let interrupted = model.interrupt_flag();
+        ctrlc::set_handler(move || {
+            if interrupted.swap(true, Ordering::SeqCst) {
+                process::exit(130);
+            }
+        })
+        .map_err(|err| Error::InvalidConfig(format!("cannot handle ctrl-c: {}", err)))?;
+    }
+    if args.resume.is_some() {
+        model.resume()?;
+    } else if let Some(epochs) = args
+        .epochs
+        .or(args.load.is_none().then_some(DEFAULT_EPOCHS))
+    {
+        // a loaded model is only trained further when asked to
+        model.train(epochs)?;
+    }
+    if model.unfinished() {
+        if let Some(path) = checkpoint {
+            println!(
+                "Training interrupted, continue it with --resume {}",
+                path.display()
+            );
+        }
+        return Ok(());
+    }
+    let metrics = model.test();
+    if let Some(path) = &args.report {
+        let format = args
+            .report_format
+            .unwrap_or_else(|| ReportFormat::from_path(path));
+        metrics.export(path, format)?;
+    }
+    if let Some(path) = &args.save {
+        model.save(path)?;
+    }
+    Ok(())
+}
+
+// train every configuration of a sweep, then print the ranked results and write them to a file
+fn run_sweep(args: &Args, sweep: SweepArgs) -> Result<()> {
+    let mut spec = match &sweep.spec {
+        Some(path) => SweepSpec::load(path)?,
+        None => SweepSpec::default(),
+    };
+    if let Some(search) = sweep.search {
+        spec.search = search;
+    }
+    if let Some(trials) = sweep.trials {
+        spec.trials = trials;
+    }
+    if !sweep.layers.is_empty() {
+        spec.layers = sweep.layers;
+    }
+    if !sweep.alpha.is_empty() {
+        spec.alpha = sweep.alpha;
+    }
+    if !sweep.batch_size.is_empty() {
+        spec.batch_size = sweep.batch_size;
+    }
+    if !sweep.epochs.is_empty() {
+        spec.epochs = sweep.epochs;
+    }
+    let trials = spec.trials(
+        &hyperparameters(args),
+        args.epochs.unwrap_or(DEFAULT_EPOCHS),
+    )?;
+    // every trial shares the seed, printed so the sweep can be repeated with --seed
+    if let Some(seed) = trials.first().and_then(|trial| trial.hyperparameters.seed) {
+        println!("Seed: {}", seed);
+    }
+    println!("Training {} configurations", trials.len());
+    let results = Sweep::run(&trials, &args.data_dir, sweep.jobs)?;
+    print!("{}", results);
+    if let Some(path) = &sweep.output {
+        results.export(path, ReportFormat::from_path(path))?;
+    }
+    Ok(())
 }
 
 fn main() {
-    let args = Args::parse();
-    let mut model = Model::new(args.layer_size, args.alpha, args.batch_size);
-    model.train(args.epochs);
-    model.test();
+    let mut args = Args::parse();
+    let result = match args.command.take() {
+        Some(Command::Predict { model, files }) => run_predict(&model, &files),
+        Some(Command::Sweep(sweep)) => run_sweep(&args, sweep),
+        None => run_training(args),
+    };
+    if let Err(err) = result {
+        eprintln!("Error: {}", err);
+        process::exit(1);
+    }
 }
@@ -0,0 +1,420 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use clap::ValueEnum;
+use ndarray::{prelude::Array2, Axis};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+// largest k tracked for top-k accuracy
+const MAX_TOP_K: usize = 5;
+
+// file formats a metrics report can be exported to
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+    Json,
+    Csv,
+}
+
+impl ReportFormat {
+    // pick the format from a file extension, defaulting to json
+    pub fn from_path(path: &Path) -> ReportFormat {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("csv") => ReportFormat::Csv,
+            _ => ReportFormat::Json,
+        }
+    }
+}
+
+// classification metrics accumulated over every batch of an epoch
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Metrics {
+    // classes x classes counts, rows are the ground truth and columns are the predictions
+    pub confusion: Array2<usize>,
+    // amount of samples whose label was within the k most probable classes, for k = 1, 2, ...
+    top_k: Vec<usize>,
+}
+
+// precision, recall and f1 score of a single class or an average
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct Scores {
+    pub precision: f32,
+    pub recall: f32,
+    pub f1: f32,
+}
+
+impl Scores {
+    fn new(precision: f32, recall: f32) -> Scores {
+        Scores {
+            precision,
+            recall,
+            f1: ratio(2f32 * precision * recall, precision + recall),
+        }
+    }
+}
+
+// everything in a metrics report, in the shape it is exported to json
+#[derive(Serialize)]
+struct Report {
+    samples: usize,
+    accuracy: f32,
+    top_k_accuracy: Vec<TopK>,
+    classes: Vec<ClassReport>,
+    macro_average: Scores,
+    micro_average: Scores,
+    confusion_matrix: Vec<Vec<usize>>,
+}
+
+#[derive(Serialize)]
+struct TopK {
+    k: usize,
+    accuracy: f32,
+}
+
+#[derive(Serialize)]
+struct ClassReport {
+    class: usize,
+    #[serde(flatten)]
+    scores: Scores,
+    support: usize,
+}
+
+impl Metrics {
+    // create empty metrics for a classifier with the given amount of classes
+    pub fn new(classes: usize) -> Metrics {
+        Metrics {
+            confusion: Array2::<usize>::zeros((classes, classes)),
+            top_k: vec![0; MAX_TOP_K.min(classes)],
+        }
+    }
+
+    // amount of classes tracked
+    pub fn classes(&self) -> usize {
+        self.confusion.nrows()
+    }
+
+    // add a batch of network outputs to the metrics
+    // output: probability of each class, one column per sample
+    // labels: 1 x samples ground truth classes
+    pub fn add_batch(&mut self, output: &Array2<f32>, labels: &Array2<f32>) {
+        for (probabilities, label) in output.axis_iter(Axis(1)).zip(labels.iter()) {
+            let label = *label as usize;
+            // rank of the labelled class, 0 when it is the most probable one
+            // ties go to the lower class like they do for the prediction, so top-1 accuracy is
+            // the accuracy
+            let rank = probabilities
+                .iter()
+                .enumerate()
+                .filter(|(i, probability)| {
+                    **probability > probabilities[label]
+                        || (**probability == probabilities[label] && *i < label)
+                })
+                .count();
+            let mut prediction = 0;
+            for (i, probability) in probabilities.iter().enumerate() {
+                if *probability > probabilities[prediction] {
+                    prediction = i;
+                }
+            }
+            self.confusion[[label, prediction]] += 1;
+            for (k, count) in self.top_k.iter_mut().enumerate() {
+                if rank <= k {
+                    *count += 1;
+                }
+            }
+        }
+    }
+
+    // amount of samples seen
+    pub fn samples(&self) -> usize {
+        self.confusion.sum()
+    }
+
+    // fraction of samples predicted correctly
+    pub fn accuracy(&self) -> f32 {
+        ratio(self.confusion.diag().sum() as f32, self.samples() as f32)
+    }
+
+    // fraction of samples whose label is within the k most probable classes
+    pub fn top_k_accuracy(&self, k: usize) -> f32 {
+        let count = self.top_k[k.clamp(1, self.top_k.len()) - 1];
+        ratio(count as f32, self.samples() as f32)
+    }
+
+    // precision, recall and f1 score of one class
+    pub fn scores(&self, class: usize) -> Scores {
+        let true_positives = self.confusion[[class, class]] as f32;
+        let predicted = self.confusion.column(class).sum() as f32;
+        let actual = self.confusion.row(class).sum() as f32;
+        Scores::new(
+            ratio(true_positives, predicted),
+            ratio(true_positives, actual),
+        )
+    }
+
+    // unweighted mean of the per class scores
+    pub fn macro_average(&self) -> Scores {
+        let classes = self.classes() as f32;
+        let (mut precision, mut recall, mut f1) = (0f32, 0f32, 0f32);
+        for class in 0..self.classes() {
+            let scores = self.scores(class);
+            precision += scores.precision;
+            recall += scores.recall;
+            f1 += scores.f1;
+        }
+        Scores {
+            precision: precision / classes,
+            recall: recall / classes,
+            f1: f1 / classes,
+        }
+    }
+
+    // scores computed from the totals over every class, every sample has exactly one label and
+    // one prediction so all three equal the accuracy
+    pub fn micro_average(&self) -> Scores {
+        Scores::new(self.accuracy(), self.accuracy())
+    }
+
+    fn report(&self) -> Report {
+        Report {
+            samples: self.samples(),
+            accuracy: self.accuracy(),
+            top_k_accuracy: (1..=self.top_k.len())
+                .map(|k| TopK {
+                    k,
+                    accuracy: self.top_k_accuracy(k),
+                })
+                .collect(),
+            classes: (0..self.classes())
+                .map(|class| ClassReport {
+                    class,
+                    scores: self.scores(class),
+                    support: self.confusion.row(class).sum(),
+                })
+                .collect(),
+            macro_average: self.macro_average(),
+            micro_average: self.micro_average(),
+            confusion_matrix: self
+                .confusion
+                .axis_iter(Axis(0))
+                .map(|row| row.to_vec())
+                .collect(),
+        }
+    }
+
+    // report as pretty printed json
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.report()).unwrap()
+    }
+
+    // report as csv, one row per class holding its scores followed by its row of the confusion
+    // matrix, and one row per average
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("class,precision,recall,f1,support");
+        for class in 0..self.classes() {
+            csv += &format!(",predicted_{}", class);
+        }
+        csv += "\n";
+        for class in 0..self.classes() {
+            let scores = self.scores(class);
+            csv += &format!(
+                "{},{},{},{},{}",
+                class,
+                scores.precision,
+                scores.recall,
+                scores.f1,
+                self.confusion.row(class).sum()
+            );
+            for count in self.confusion.row(class) {
+                csv += &format!(",{}", count);
+            }
+            csv += "\n";
+        }
+        for (name, scores) in [
+            ("macro", self.macro_average()),
+            ("micro", self.micro_average()),
+        ] {
+            csv += &format!(
+                "{},{},{},{},{}\n",
+                name,
+                scores.precision,
+                scores.recall,
+                scores.f1,
+                self.samples()
+            );
+        }
+        csv
+    }
+
+    // write the report to a file
+    pub fn export(&self, path: &Path, format: ReportFormat) -> Result<()> {
+        let contents = match format {
+            ReportFormat::Json => self.to_json(),
+            ReportFormat::Csv => self.to_csv(),
+        };
+        fs::write(path, contents).map_err(|err| Error::io(path, err))
+    }
+}
+
+// table of per class scores, averages, top-k accuracy and the confusion matrix
+impl fmt::Display for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "-----------------------------")?;
+        writeln!(
+            f,
+            "{:<7}{:>10}{:>10}{:>10}{:>10}",
+            "Class", "Precision", "Recall", "F1", "Support"
+        )?;
+        for class in 0..self.classes() {
+            let scores = self.scores(class);
+            writeln!(
+                f,
+                "{:<7}{:>10.4}{:>10.4}{:>10.4}{:>10}",
+                class,
+                scores.precision,
+                scores.recall,
+                scores.f1,
+                self.confusion.row(class).sum()
+            )?;
+        }
+        for (name, scores) in [
+            ("Macro", self.macro_average()),
+            ("Micro", self.micro_average()),
+        ] {
+            writeln!(
+                f,
+                "{:<7}{:>10.4}{:>10.4}{:>10.4}{:>10}",
+                name,
+                scores.precision,
+                scores.recall,
+                scores.f1,
+                self.samples()
+            )?;
+        }
+        writeln!(f, "-----------------------------")?;
+        writeln!(
+            f,
+            "Confusion matrix (rows: ground truth, columns: predicted)"
+        )?;
+        write!(f, "{:<7}", "")?;
+        for class in 0..self.classes() {
+            write!(f, "{:>7}", class)?;
+        }
+        writeln!(f)?;
+        for (class, row) in self.confusion.axis_iter(Axis(0)).enumerate() {
+            write!(f, "{:<7}", class)?;
+            for count in row {
+                write!(f, "{:>7}", count)?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "-----------------------------")?;
+        for k in 1..=self.top_k.len() {
+            writeln!(f, "Top-{} accuracy: {}", k, self.top_k_accuracy(k))?;
+        }
+        Ok(())
+    }
+}
+
+// division that treats 0 / 0 as 0, for classes that were never predicted or never seen
+fn ratio(numerator: f32, denominator: f32) -> f32 {
+    if denominator == 0f32 {
+        0f32
+    } else {
+        numerator / denominator
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{arr2, s, Array};
+
+    // 7 samples of 4 classes, where class 3 is never the label nor the prediction
+    // rows are the ground truth, columns the predictions:
+    //   [1, 1, 0, 0]
+    //   [1, 1, 0, 0]
+    //   [2, 0, 1, 0]
+    //   [0, 0, 0, 0]
+    fn metrics() -> Metrics {
+        let output = arr2(&[
+            [0.7, 0.3, 0.4, 0.1, 0.5, 0.1, 0.5],
+            [0.2, 0.6, 0.4, 0.8, 0.1, 0.2, 0.3],
+            [0.1, 0.1, 0.2, 0.1, 0.4, 0.7, 0.2],
+            [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+        ]);
+        // the third sample ties between classes 0 and 1 and is predicted as 0
+        let labels = Array::from_shape_vec((1, 7), vec![0., 0., 1., 1., 2., 2., 2.]).unwrap();
+        let mut metrics = Metrics::new(4);
+        // split over two batches, which add up the same as one
+        metrics.add_batch(
+            &output.slice(s![.., ..3]).to_owned(),
+            &labels.slice(s![.., ..3]).to_owned(),
+        );
+        metrics.add_batch(
+            &output.slice(s![.., 3..]).to_owned(),
+            &labels.slice(s![.., 3..]).to_owned(),
+        );
+        metrics
+    }
+
+    fn assert_scores(scores: Scores, precision: f32, recall: f32, f1: f32) {
+        for (found, expected) in [
+            (scores.precision, precision),
+            (scores.recall, recall),
+            (scores.f1, f1),
+        ] {
+            assert!(
+                (found - expected).abs() < 1e-6,
+                "{:?} is not {} {} {}",
+                scores,
+                precision,
+                recall,
+                f1
+            );
+        }
+    }
+
+    #[test]
+    fn add_batch_fills_the_confusion_matrix() {
+        let metrics = metrics();
+        assert_eq!(
+            metrics.confusion,
+            arr2(&[[1, 1, 0, 0], [1, 1, 0, 0], [2, 0, 1, 0], [0, 0, 0, 0]])
+        );
+        assert_eq!(metrics.samples(), 7);
+        assert_eq!(metrics.accuracy(), 3.0 / 7.0);
+    }
+
+    #[test]
+    fn scores_per_class() {
+        let metrics = metrics();
+        assert_scores(metrics.scores(0), 0.25, 0.5, 1.0 / 3.0);
+        assert_scores(metrics.scores(1), 0.5, 0.5, 0.5);
+        assert_scores(metrics.scores(2), 1.0, 1.0 / 3.0, 0.5);
+        // a class that never appears scores 0 rather than dividing by 0
+        assert_scores(metrics.scores(3), 0.0, 0.0, 0.0);
+    }
+
+    #[test]
+    fn averages_over_classes() {
+        let metrics = metrics();
+        assert_scores(metrics.macro_average(), 0.4375, 1.0 / 3.0, 1.0 / 3.0);
+        assert_scores(metrics.micro_average(), 3.0 / 7.0, 3.0 / 7.0, 3.0 / 7.0);
+    }
+
+    #[test]
+    fn top_k_accuracy_counts_labels_within_the_k_most_probable() {
+        let metrics = metrics();
+        // the tied sample ranks its label second, like its prediction does
+        assert_eq!(metrics.top_k_accuracy(1), metrics.accuracy());
+        assert_eq!(metrics.top_k_accuracy(2), 6.0 / 7.0);
+        assert_eq!(metrics.top_k_accuracy(3), 1.0);
+        // k is clamped to the classes tracked
+        assert_eq!(metrics.top_k_accuracy(4), 1.0);
+        assert_eq!(metrics.top_k_accuracy(10), 1.0);
+        assert_eq!(Metrics::new(4).top_k_accuracy(1), 0.0);
+    }
+}
@@ -1,176 +1,1040 @@
-use crate::layers::{dataset::Dataset, relu::ReLU, softmax::Softmax};
-use ndarray::{prelude::Array2, Axis};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::augmentation::Augmentation;
+use crate::checkpoint::{
+    Checkpoint, LayerCheckpoint, LayerSpec, TrainingState, CHECKPOINT_VERSION,
+};
+use crate::error::{Error, Result};
+use crate::layers::{
+    activation::Activation,
+    batchnorm::BatchNorm,
+    conv2d::Conv2d,
+    dataset::{Batches, Dataset, DatasetKind},
+    dropout::Dropout,
+    initializer::Initializer,
+    layer::Layer,
+    maxpool2d::MaxPool2d,
+    softmax::Softmax,
+};
+use crate::logger::{LogEntry, LogKind, TrainingLog};
+use crate::loss::Loss;
+use crate::metrics::Metrics;
+use crate::optimizer::{Optimizer, OptimizerKind};
+use crate::scheduler::{Scheduler, SchedulerKind};
+use crate::sequential::Sequential;
+use ndarray::prelude::Array2;
+use ndarray_rand::rand::{self, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+// weight of each new batch in the running statistics of batch normalization
+const BATCH_NORM_MOMENTUM: f32 = 0.1;
 
 // Used to specify whether or not the training data and forward prop should
-// be ran with training or testing data
+// be ran with training, validation or testing data
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy)]
 pub enum CONFIG {
     TRAIN,
+    VALIDATE,
     TEST,
 }
 
-// 2 layer neural network, with accuracy vector for tracking performance
+// hyperparameters used to build and train a model, saved alongside it in checkpoints
+// missing fields in older checkpoints fall back to the defaults
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Hyperparameters {
+    // dataset the model is trained and tested on
+    pub dataset: DatasetKind,
+    // amount of filters of each convolution, each followed by 2x2 max pooling, in order
+    pub conv: Vec<usize>,
+    // width and height of each convolution's filters
+    pub kernel_size: usize,
+    // size of each hidden layer after the convolutions, in order
+    pub layers: Vec<usize>,
+    // activation function of the hidden layers
+    pub activation: Activation,
+    // seed of the random number generator behind initialization, shuffling, dropout and
+    // augmentation, picked at random and recorded when not given
+    pub seed: Option<u64>,
+    // how the weights of each layer with weights are drawn, in order: the convolutions, the
+    // hidden layers and the output layer
+    // a single initializer is used for every convolution and hidden layer, and layers without
+    // one use an initializer that suits their activation
+    pub initializers: Vec<Initializer>,
+    // normalize the output of every convolution and hidden layer with batch normalization
+    pub batch_norm: bool,
+    // probability of dropping each node of the hidden layers while training, 0 adds no dropout
+    pub dropout: f32,
+    // learning rate of network
+    pub alpha: f32,
+    // size of each slice
+    pub batch_size: isize,
+    // loss function minimized during training
+    pub loss: Loss,
+    // update rule for weights and biases
+    pub optimizer: OptimizerKind,
+    // momentum coefficient, and beta1 for adam
+    pub momentum: f32,
+    // decay of the running average of squared gradients for rmsprop and adam
+    pub beta2: f32,
+    // l2 penalty on weights and biases
+    pub weight_decay: f32,
+    // fraction of the training set held out for validation
+    pub validation_split: f32,
+    // amount of epochs between evaluations on the validation set
+    pub validate_every: usize,
+    // schedule the learning rate follows, starting from alpha
+    pub scheduler: SchedulerKind,
+    // epochs between decays for the step schedule, length of each cosine cycle
+    pub step_size: usize,
+    // decay factor of the step, exponential and plateau schedules
+    pub gamma: f32,
+    // lowest learning rate the schedules decay to
+    pub min_alpha: f32,
+    // epochs the learning rate is linearly increased over at the start of training
+    pub warmup: usize,
+    // validations without a lower validation loss before the plateau schedule decays
+    pub plateau_patience: usize,
+    // stop after this many validations without a lower validation loss and restore the best
+    // weights, 0 disables early stopping
+    pub patience: usize,
+    // random transformations applied to every training sample, none by default
+    pub augmentation: Augmentation,
+}
+
+impl Default for Hyperparameters {
+    fn default() -> Self {
+        Hyperparameters {
+            dataset: DatasetKind::Mnist,
+            conv: Vec::new(),
+            kernel_size: 5,
+            layers: vec![128],
+            activation: Activation::ReLU,
+            seed: None,
+            initializers: Vec::new(),
+            batch_norm: false,
+            dropout: 0.0,
+            alpha: 0.01,
+            batch_size: 100,
+            loss: Loss::CrossEntropy,
+            optimizer: OptimizerKind::Sgd,
+            momentum: 0.9,
+            beta2: 0.999,
+            weight_decay: 0.0,
+            scheduler: SchedulerKind::Constant,
+            step_size: 10,
+            gamma: 0.1,
+            min_alpha: 0.0,
+            warmup: 0,
+            plateau_patience: 2,
+            validation_split: 0.0,
+            validate_every: 1,
+            patience: 0,
+            augmentation: Augmentation::default(),
+        }
+    }
+}
+
+// position of a run of Model::train that has not finished yet
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TrainingRun {
+    // epoch counter the run stops at
+    pub end_epoch: usize,
+    // batches of the current epoch that have not been trained on, none between epochs
+    pub batches: Option<Batches>,
+    // lowest validation loss so far, with the weights it was reached with
+    pub best: Option<(f32, Vec<LayerCheckpoint>)>,
+    // epoch counter the lowest validation loss was reached at
+    pub best_epoch: Option<usize>,
+    // validations since the best one
+    pub stale: usize,
+    // seconds spent training before the run was last resumed
+    #[serde(default)]
+    pub elapsed: f64,
+}
+
+// sequential neural network of convolutional and hidden layers and a Softmax output layer,
+// with metrics for tracking performance
 pub struct Model {
-    // dataset struct holding full data and slices
-    dataset: Dataset,
-    // ReLU layer
-    hidden_layer: ReLU,
-    // Softmax layer
-    output_layer: Softmax,
-    // 10 x 2 vector for tracking accuracy
-    accuracy: Array2<f32>,
+    // dataset struct holding full data, shared with other models trained on it at the same time
+    dataset: Arc<Dataset>,
+    // hidden layers followed by the output layer
+    network: Sequential,
+    // update rule shared by every layer
+    optimizer: Optimizer,
+    // sets the learning rate of the optimizer before every update
+    scheduler: Scheduler,
+    // confusion matrix and top-k counts of the current epoch
+    metrics: Metrics,
+    // summed loss and amount of samples it was summed over, for tracking the mean loss
+    loss_sum: f32,
+    loss_samples: f32,
+    // summed gradient norm of every batch, weighted by its samples like the loss
+    gradient_norm_sum: f32,
+    // hyperparameters the model was created with
+    hyperparameters: Hyperparameters,
+    // amount of epochs trained so far
+    epoch: usize,
+    // epoch whose weights early stopping restored at the end of the last run, if it did
+    best_epoch: Option<usize>,
+    // source of every random number drawn while training, seeded from the hyperparameters
+    rng: ChaCha8Rng,
+    // run of train that was interrupted, or loaded from a checkpoint saved during one
+    run: Option<TrainingRun>,
+    // file the run is saved to every few epochs and when interrupted, with the epochs between
+    // saves, 0 only saving when interrupted
+    autosave: Option<(PathBuf, usize)>,
+    // set to stop training after the current batch
+    interrupted: Arc<AtomicBool>,
+    // file a row is written to for every epoch, and optionally every batch, of training
+    log: Option<TrainingLog>,
+    // print the progress of training
+    verbose: bool,
 }
 
 impl Model {
-    // creates a new layer, letting each layer's constructors handle the initialization
-    // layer_size: specifies size of ReLU layer
-    // alpha: specifies learning rate of network
-    // slice_range: specifies size of each slice
-    pub fn new(layer_size: usize, alpha: f32, slice_range: isize) -> Model {
-        let mut dataset = Dataset::new(slice_range);
-        dataset.set_slice(CONFIG::TRAIN);
-        let hidden_layer = ReLU::new(784, layer_size, slice_range as usize, alpha, 0.01);
-        let output_layer = Softmax::new(layer_size, 10, slice_range as usize, alpha);
-        let accuracy = Array2::<f32>::zeros((10, 2));
-        Model {
-            dataset,
-            hidden_layer,
-            output_layer,
-            accuracy,
-        }
+    // creates a new model, drawing the weights of every layer in order from a single rng
+    // hyperparameters: specifies layer sizes, learning rate and slice size
+    // data_dir: directory holding the idx files of the dataset
+    pub fn new(mut hyperparameters: Hyperparameters, data_dir: &Path) -> Result<Model> {
+        let dataset = Dataset::new(
+            data_dir,
+            hyperparameters.dataset,
+            hyperparameters.validation_split,
+        )?;
+        let network = Model::build_network(&mut hyperparameters, &dataset)?;
+        Model::from_network(Arc::new(dataset), network, hyperparameters, 0)
     }
 
-    // forward propogration function, mostly handled in layer
-    // configuration: specifies whether or not network is training
-    fn forward_prop(&mut self, configuration: CONFIG) {
-        match configuration {
-            CONFIG::TRAIN => {
-                self.hidden_layer
-                    .forward_prop(&self.dataset.train_data_slice);
-                self.output_layer.forward_prop(&self.hidden_layer.layer);
+    // build the network the hyperparameters describe for the images and classes of a dataset,
+    // drawing the weights of every layer in order from a single rng
+    // without a seed in the hyperparameters, one is picked at random and recorded in them
+    pub fn build_network(
+        hyperparameters: &mut Hyperparameters,
+        dataset: &Dataset,
+    ) -> Result<Sequential> {
+        // the weights are drawn from the first stream of the seed, training uses later ones
+        let seed = *hyperparameters.seed.get_or_insert_with(rand::random);
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let hyperparameters = &*hyperparameters;
+        let initializers = &hyperparameters.initializers;
+        let weighted_layers = hyperparameters.conv.len() + hyperparameters.layers.len() + 1;
+        if initializers.len() > 1 && initializers.len() != weighted_layers {
+            return Err(Error::InvalidConfig(format!(
+                "got {} initializers, give a single one or one for each of the {} layers with \
+                 weights",
+                initializers.len(),
+                weighted_layers
+            )));
+        }
+        // initializer of the i-th layer with weights
+        let initializer = |i: usize, default: Initializer| {
+            if initializers.len() == weighted_layers {
+                initializers[i]
+            } else if initializers.len() == 1 && i + 1 < weighted_layers {
+                initializers[0]
+            } else {
+                default
+            }
+        };
+        let mut network = Sequential::new();
+        // shape of the images going into each convolution
+        let (mut channels, mut height, mut width) = (1, dataset.rows, dataset.cols);
+        let kernel = hyperparameters.kernel_size;
+        for (i, &filters) in hyperparameters.conv.iter().enumerate() {
+            if kernel == 0 || height < kernel + 1 || width < kernel + 1 {
+                return Err(Error::InvalidConfig(format!(
+                    "a {}x{} kernel followed by 2x2 pooling does not fit {}x{} feature maps",
+                    kernel, kernel, height, width
+                )));
+            }
+            let conv = Conv2d::new(
+                channels,
+                height,
+                width,
+                filters,
+                kernel,
+                0,
+                0.01,
+                initializer(i, Initializer::HeNormal),
+                &mut rng,
+            )?;
+            let (conv_height, conv_width) = (conv.out_height(), conv.out_width());
+            let pool = MaxPool2d::new(filters, conv_height, conv_width, 2)?;
+            (channels, height, width) = (filters, pool.out_height(), pool.out_width());
+            network.add(Box::new(conv))?;
+            if hyperparameters.batch_norm {
+                network.add(Box::new(BatchNorm::new(
+                    filters,
+                    conv_height * conv_width,
+                    BATCH_NORM_MOMENTUM,
+                )?))?;
+            }
+            network.add(Box::new(pool))?;
+        }
+        // each hidden layer takes the nodes of the layer before it as input
+        let mut input = channels * height * width;
+        for (i, &layer_size) in hyperparameters.layers.iter().enumerate() {
+            network.add(hyperparameters.activation.build(
+                input,
+                layer_size,
+                initializer(
+                    hyperparameters.conv.len() + i,
+                    hyperparameters.activation.initializer(),
+                ),
+                &mut rng,
+            )?)?;
+            if hyperparameters.batch_norm {
+                network.add(Box::new(BatchNorm::new(
+                    layer_size,
+                    1,
+                    BATCH_NORM_MOMENTUM,
+                )?))?;
             }
-            CONFIG::TEST => {
-                self.hidden_layer
-                    .forward_prop(&self.dataset.test_data_slice);
-                self.output_layer.forward_prop(&self.hidden_layer.layer);
+            if hyperparameters.dropout > 0.0 {
+                network.add(Box::new(Dropout::new(layer_size, hyperparameters.dropout)?))?;
             }
+            input = layer_size;
         }
+        network.add(Box::new(Softmax::new(
+            input,
+            dataset.classes,
+            initializer(weighted_layers - 1, Initializer::XavierNormal),
+            &mut rng,
+        )?))?;
+        Ok(network)
     }
 
-    // backwards propogation function, mostly handled in layer
-    fn backward_prop(&mut self) {
-        self.output_layer
-            .backward_prop(&self.dataset.train_label_slice, &self.hidden_layer.layer);
-        self.hidden_layer
-            .backward_prop(&self.output_layer.layer, &self.dataset.train_data_slice);
+    // wraps an already built network together with the dataset, which has to take the dataset's
+    // images as input and output a probability for each of its classes
+    // without a seed in the hyperparameters, one is picked at random and recorded in them
+    pub fn from_network(
+        dataset: Arc<Dataset>,
+        network: Sequential,
+        mut hyperparameters: Hyperparameters,
+        epoch: usize,
+    ) -> Result<Model> {
+        let (Some(input_size), Some(output_size)) = (network.input_size(), network.output_size())
+        else {
+            return Err(Error::ShapeMismatch("network has no layers".to_string()));
+        };
+        network.validate()?;
+        let pixels = dataset.rows * dataset.cols;
+        if input_size != pixels {
+            return Err(Error::ShapeMismatch(format!(
+                "network takes {} inputs, but the dataset's images are {}x{} pixels",
+                input_size, dataset.rows, dataset.cols
+            )));
+        }
+        hyperparameters.augmentation.validate()?;
+        if hyperparameters.batch_size < 1 {
+            return Err(Error::InvalidConfig(format!(
+                "batch size has to be at least 1, got {}",
+                hyperparameters.batch_size
+            )));
+        }
+        if hyperparameters.scheduler == SchedulerKind::Plateau
+            && dataset.samples(CONFIG::VALIDATE) == 0
+        {
+            return Err(Error::InvalidConfig(
+                "the plateau schedule needs a validation split to watch".to_string(),
+            ));
+        }
+        if output_size != dataset.classes {
+            return Err(Error::ShapeMismatch(format!(
+                "network has {} outputs, but the dataset has {} classes",
+                output_size, dataset.classes
+            )));
+        }
+        // a model loaded after some epochs continues on its own stream, so it does not repeat the
+        // random numbers of the epochs before
+        let seed = *hyperparameters.seed.get_or_insert_with(rand::random);
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        rng.set_stream(1 + epoch as u64);
+        let metrics = Metrics::new(output_size);
+        let optimizer = Optimizer::new(
+            hyperparameters.optimizer,
+            hyperparameters.alpha,
+            hyperparameters.momentum,
+            hyperparameters.beta2,
+            hyperparameters.weight_decay,
+        );
+        let scheduler = Scheduler::new(
+            hyperparameters.scheduler,
+            hyperparameters.alpha,
+            hyperparameters.step_size,
+            hyperparameters.gamma,
+            hyperparameters.min_alpha,
+            hyperparameters.warmup,
+            hyperparameters.plateau_patience,
+        );
+        Ok(Model {
+            dataset,
+            network,
+            optimizer,
+            scheduler,
+            metrics,
+            loss_sum: 0f32,
+            loss_samples: 0f32,
+            gradient_norm_sum: 0f32,
+            hyperparameters,
+            epoch,
+            best_epoch: None,
+            rng,
+            run: None,
+            autosave: None,
+            interrupted: Arc::new(AtomicBool::new(false)),
+            log: None,
+            verbose: true,
+        })
     }
-    // updating of weights and biases
-    fn update_params(&mut self) {
-        self.output_layer.layer.update_params();
-        self.hidden_layer.layer.update_params();
-    }
-
-    // gets predictions from softmaxed output layer in label format
-    fn get_predictions(&self) -> Array2<f32> {
-        let mut out = Array2::<f32>::zeros((1, self.output_layer.layer.layer.ncols()));
-        // loop for finding indexes of the max value in each row of the output layer
-        for i in 0..self.output_layer.layer.layer.ncols() {
-            let mut max_index = 0.0;
-            let mut max_value = 0.0;
-            for j in 0..self.output_layer.layer.layer.nrows() {
-                if self.output_layer.layer.layer[[j, i]] > max_value {
-                    max_index = j as f32;
-                    max_value = self.output_layer.layer.layer[[j, i]];
-                }
-            }
-            out[[0, i]] = max_index;
+
+    // network being trained, for running inference on it directly
+    pub fn network(&self) -> &Sequential {
+        &self.network
+    }
+
+    pub fn network_mut(&mut self) -> &mut Sequential {
+        &mut self.network
+    }
+
+    // dataset the model trains and tests on
+    pub fn dataset(&self) -> &Dataset {
+        &self.dataset
+    }
+
+    // hyperparameters the model was created with
+    pub fn hyperparameters(&self) -> &Hyperparameters {
+        &self.hyperparameters
+    }
+
+    // amount of epochs trained so far
+    pub fn epoch(&self) -> usize {
+        self.epoch
+    }
+
+    // epoch whose weights early stopping restored at the end of the last run, if it did
+    pub fn best_epoch(&self) -> Option<usize> {
+        self.best_epoch
+    }
+
+    // seed every random number of the model is drawn from
+    pub fn seed(&self) -> u64 {
+        self.hyperparameters
+            .seed
+            .expect("seed is picked when the model is created")
+    }
+
+    // whether a run of train was interrupted before finishing, it can be continued with resume
+    pub fn unfinished(&self) -> bool {
+        self.run.is_some()
+    }
+
+    // save the model every few epochs while training, and when interrupted, so the run can be
+    // resumed from the file
+    // every: epochs between saves, 0 only saves when interrupted
+    pub fn autosave(&mut self, path: PathBuf, every: usize) {
+        self.autosave = Some((path, every));
+    }
+
+    // whether training prints its progress, on by default
+    pub fn set_verbose(&mut self, verbose: bool) {
+        self.verbose = verbose;
+    }
+
+    // write the loss, accuracy, learning rate, gradient norm and time of every epoch of training,
+    // and of every batch when the log asks for steps, to a log file
+    pub fn log_to(&mut self, log: TrainingLog) {
+        self.log = Some(log);
+    }
+
+    // flag that stops training after the current batch once set, saving the run first when
+    // autosave is on, e.g. from a ctrl-c handler
+    pub fn interrupt_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.interrupted)
+    }
+
+    // save architecture, hyperparameters, parameters and epoch counter to a checkpoint file,
+    // along with the state needed to resume training when a run has not finished
+    pub fn save(&self, path: &Path) -> Result<()> {
+        self.save_run(path, self.run.as_ref())
+    }
+
+    // save the model as it is in the middle of a run
+    fn save_run(&self, path: &Path, run: Option<&TrainingRun>) -> Result<()> {
+        Checkpoint {
+            version: CHECKPOINT_VERSION,
+            hyperparameters: self.hyperparameters.clone(),
+            epoch: self.epoch,
+            layers: self.network.checkpoint(),
+            training: run.map(|run| TrainingState {
+                run: run.clone(),
+                optimizer: self.optimizer.clone(),
+                scheduler: self.scheduler.clone(),
+                optimizer_states: self.network.optimizer_states(),
+                rng: self.rng.clone(),
+                loss_sum: self.loss_sum,
+                loss_samples: self.loss_samples,
+                metrics: self.metrics.clone(),
+                gradient_norm_sum: self.gradient_norm_sum,
+            }),
         }
-        out
+        .save(path)
     }
 
-    // adds batch accuracy to total accuracy measurement per epoch
-    fn set_accuracy(&mut self, predictions: Array2<f32>, ground_truth: Array2<f32>) {
-        // make sure that predictions and ground_truth are same size
-        assert!(predictions.ncols() == ground_truth.ncols());
-        for ((i, j), item) in predictions.indexed_iter() {
-            if *item == ground_truth[[i, j]] {
-                // adds to column 0 if predicition is correct
-                self.accuracy[[ground_truth[[i, j]] as usize, 0]] += 1f32;
+    // recreate a model from a checkpoint file written by save, including the state of an
+    // unfinished run of training
+    // data_dir: directory holding the idx files of the dataset
+    pub fn load(path: &Path, data_dir: &Path) -> Result<Model> {
+        let checkpoint = Checkpoint::load(path)?;
+        let network = Sequential::from_checkpoint(&checkpoint.layers)?;
+        let dataset = Dataset::new(
+            data_dir,
+            checkpoint.hyperparameters.dataset,
+            checkpoint.hyperparameters.validation_split,
+        )?;
+        let mut model = Model::from_network(
+            Arc::new(dataset),
+            network,
+            checkpoint.hyperparameters,
+            checkpoint.epoch,
+        )?;
+        if let Some(training) = checkpoint.training {
+            let batches_fit =
+                training.run.batches.as_ref().is_none_or(|batches| {
+                    batches.samples() == model.dataset.samples(CONFIG::TRAIN)
+                });
+            if !batches_fit
+                || training.metrics.classes() != model.dataset.classes
+                || training.optimizer_states.len() != model.network.layers.len()
+            {
+                return Err(Error::invalid_file(
+                    path,
+                    "training state does not match the saved network".to_string(),
+                ));
+            }
+            if training
+                .run
+                .batches
+                .as_ref()
+                .is_some_and(|batches| !batches.is_valid())
+            {
+                return Err(Error::invalid_file(
+                    path,
+                    "batches left in the saved epoch refer to samples outside the training set"
+                        .to_string(),
+                ));
             }
-            // adds all instances to column 1 for accurate count of samples
-            self.accuracy[[ground_truth[[i, j]] as usize, 1]] += 1f32;
+            model
+                .network
+                .load_optimizer_states(&training.optimizer_states);
+            model.optimizer = training.optimizer;
+            model.scheduler = training.scheduler;
+            model.rng = training.rng;
+            model.loss_sum = training.loss_sum;
+            model.loss_samples = training.loss_samples;
+            model.metrics = training.metrics;
+            model.gradient_norm_sum = training.gradient_norm_sum;
+            model.run = Some(training.run);
         }
+        Ok(model)
     }
 
-    // gets accuracy of entire epoch for display
-    fn get_accuracy(&mut self) -> f32 {
-        let mut i = 0;
-        let mut dataset_size = 0f32;
-        let mut sum = 0f32;
-        println!("-----------------------------");
-        for item in self.accuracy.axis_iter(Axis(0)) {
-            println!(
-                "Digit {}: {} out of {}, {}",
-                i,                               // digit
-                item[0],                         // total detected
-                item[1],                         // total in ground truth
-                item[0] as f32 / item[1] as f32  // digit-wise accuracy
-            );
-            sum += item[0];
-            dataset_size += item[1];
-            i += 1;
+    // output layer of the network, which from_network made sure exists
+    fn output(network: &Sequential) -> &Layer {
+        network
+            .output()
+            .expect("model networks have at least one layer")
+    }
+
+    // backwards propogation function, mostly handled in the network
+    // data, labels: batch used in the last forward pass
+    fn backward_prop(&mut self, data: &Layer, labels: &Layer) {
+        let output = &Model::output(&self.network).layer;
+        let targets = Layer::one_hot(&labels.layer, self.dataset.classes).layer;
+        let loss = self.hyperparameters.loss;
+        // a softmax output is fused with the loss, so its gradient survives underflow
+        let softmax = matches!(
+            self.network.layers.last().map(|layer| layer.spec()),
+            Some(LayerSpec::Softmax { .. })
+        );
+        if softmax {
+            let gradient = Layer::dummy_layer(loss.softmax_gradient(output, &targets));
+            self.network.backward_prop_preactivation(&gradient, data);
+        } else {
+            let gradient = Layer::dummy_layer(loss.gradient(output, &targets));
+            self.network.backward_prop(&gradient, data);
         }
-        println!("-----------------------------");
-        return sum / dataset_size;
+    }
+    // updating of weights and biases
+    fn update_params(&mut self) {
+        self.optimizer.next_step();
+        self.network.update_params(&self.optimizer);
+    }
+
+    // adds batch predictions to the metrics of the epoch
+    fn set_metrics(&mut self, ground_truth: &Array2<f32>) {
+        self.metrics
+            .add_batch(&Model::output(&self.network).layer, ground_truth);
+    }
+
+    // adds batch loss to total loss measurement per epoch, returning the loss of the batch
+    fn set_loss(&mut self, ground_truth: &Array2<f32>) -> f32 {
+        let samples = ground_truth.ncols() as f32;
+        let loss = self.hyperparameters.loss.loss(
+            &Model::output(&self.network).layer,
+            &Layer::one_hot(ground_truth, self.dataset.classes).layer,
+        );
+        // weight by batch size so the mean is taken over samples rather than batches
+        self.loss_sum += loss * samples;
+        self.loss_samples += samples;
+        loss
+    }
+
+    // gets mean loss of entire epoch for display
+    pub fn get_loss(&self) -> f32 {
+        self.loss_sum / self.loss_samples
+    }
+
+    // clears accuracy and loss measurements for the next epoch
+    fn reset_metrics(&mut self) {
+        self.metrics = Metrics::new(self.metrics.classes());
+        self.loss_sum = 0f32;
+        self.loss_samples = 0f32;
+        self.gradient_norm_sum = 0f32;
     }
 
     // train the network, and print accuracy every 10 epochs
-    pub fn train(&mut self, epochs: usize) {
-        for i in 0..epochs {
-            self.dataset.shuffle();
-            for _ in
-                0..(self.dataset.training_data.layer.ncols() / self.dataset.slice_range as usize)
-            {
-                // get new dataset slice
-                self.dataset.set_slice(CONFIG::TRAIN);
+    // with a validation set, evaluate it every validate_every epochs, stopping early and restoring
+    // the weights with the lowest validation loss once patience runs out
+    // fails if the loss stops being finite, since no further training can recover from that
+    // once the interrupt flag is set, training stops after the current batch and can be continued
+    // with resume
+    pub fn train(&mut self, epochs: usize) -> Result<()> {
+        self.run = Some(TrainingRun {
+            end_epoch: self.epoch + epochs,
+            batches: None,
+            best: None,
+            best_epoch: None,
+            stale: 0,
+            elapsed: 0.0,
+        });
+        self.best_epoch = None;
+        self.reset_metrics();
+        self.resume()
+    }
+
+    // continue an interrupted run of train, or one saved to a checkpoint before it finished,
+    // exactly where it stopped
+    pub fn resume(&mut self) -> Result<()> {
+        let Some(mut run) = self.run.take() else {
+            return Err(Error::InvalidConfig(
+                "there is no unfinished training to resume".to_string(),
+            ));
+        };
+        // an interrupt that stopped an earlier run must not stop this one straight away
+        self.interrupted.store(false, Ordering::SeqCst);
+        let validate = self.dataset.samples(CONFIG::VALIDATE) > 0;
+        let validate_every = self.hyperparameters.validate_every.max(1);
+        // wall time of the run, counting the time before it was interrupted
+        let (started, elapsed) = (Instant::now(), run.elapsed);
+        let time = || elapsed + started.elapsed().as_secs_f64();
+        while self.epoch < run.end_epoch {
+            // every training sample once, in a new random order each epoch
+            let mut batches = match run.batches.take() {
+                Some(batches) => batches,
+                None => self.dataset.batches(
+                    CONFIG::TRAIN,
+                    self.hyperparameters.batch_size as usize,
+                    &mut self.rng,
+                ),
+            };
+            // validation switches the network to evaluating, so switch back every epoch
+            self.network.set_mode(CONFIG::TRAIN);
+            loop {
+                if self.interrupted.load(Ordering::SeqCst) {
+                    // keep the batches that are left, so resuming finishes this epoch first
+                    run.batches = Some(batches);
+                    run.elapsed = time();
+                    if let Some(log) = &mut self.log {
+                        log.flush()?;
+                    }
+                    if let Some((path, _)) = &self.autosave {
+                        self.save_run(path, Some(&run))?;
+                        println!("Saved training progress to {}", path.display());
+                    }
+                    self.run = Some(run);
+                    return Ok(());
+                }
+                // epochs trained before this batch, which its learning rate is scheduled at
+                let progress = self.epoch as f32 + batches.progress();
+                let Some(indices) = batches.next() else {
+                    break;
+                };
+                let (mut data, labels) = self.dataset.batch(CONFIG::TRAIN, &indices);
+                // augment the training samples, the validation and test sets stay untouched
+                self.hyperparameters.augmentation.apply(
+                    &mut data.layer,
+                    self.dataset.rows,
+                    self.dataset.cols,
+                    &mut self.rng,
+                );
+                // layers like dropout draw from the model's rng too, so it alone holds the state of
+                // every random number of the run
+                self.network.reseed(&mut self.rng);
                 // forward
-                self.forward_prop(CONFIG::TRAIN);
+                self.network.forward_prop(&data);
+                // tally loss for batch before the parameters change
+                let loss = self.set_loss(&labels.layer);
                 // calculate gradients
-                self.backward_prop();
-                // update
+                self.backward_prop(&data, &labels);
+                let gradient_norm = self.network.gradient_norm();
+                self.gradient_norm_sum += gradient_norm * labels.layer.ncols() as f32;
+                // update, with the learning rate at the start of this batch
+                self.optimizer.alpha = self.scheduler.rate(progress);
                 self.update_params();
                 // tally accuracy for batch
-                self.set_accuracy(
-                    self.get_predictions(),
-                    self.dataset.train_label_slice.layer.clone(),
-                );
+                self.set_metrics(&labels.layer);
+                if self.log.as_ref().is_some_and(|log| log.steps) {
+                    let mut metrics = Metrics::new(self.dataset.classes);
+                    metrics.add_batch(&Model::output(&self.network).layer, &labels.layer);
+                    self.log(LogEntry {
+                        kind: LogKind::Step,
+                        epoch: self.epoch,
+                        step: self.optimizer.step(),
+                        loss,
+                        accuracy: metrics.accuracy(),
+                        validation_loss: None,
+                        validation_accuracy: None,
+                        learning_rate: self.optimizer.alpha,
+                        gradient_norm,
+                        time: time(),
+                    })?;
+                }
             }
-            if i % 10 == 0 {
+            if self.verbose && self.epoch.is_multiple_of(10) {
                 // print accuracy
                 println!("\n\n-----------------------------");
-                println!("Total Epochs: {}", i);
-                println!("Accuracy: {}", self.get_accuracy(),);
+                println!("Total Epochs: {}", self.epoch);
+                println!("Accuracy: {}", self.metrics.accuracy());
+                println!("Loss: {}", self.get_loss());
+                println!("Learning rate: {}", self.optimizer.alpha);
+            }
+            if !self.get_loss().is_finite() {
+                return Err(Error::Diverged { epoch: self.epoch });
+            }
+            let (loss, accuracy) = (self.get_loss(), self.metrics.accuracy());
+            let gradient_norm = self.gradient_norm_sum / self.loss_samples;
+            // reset accuracy and loss for next epoch
+            self.reset_metrics();
+            self.epoch += 1;
+
+            let validation = if validate && self.epoch.is_multiple_of(validate_every) {
+                Some(self.validate())
+            } else {
+                None
+            };
+            self.log(LogEntry {
+                kind: LogKind::Epoch,
+                epoch: self.epoch,
+                step: self.optimizer.step(),
+                loss,
+                accuracy,
+                validation_loss: validation.map(|(loss, _)| loss),
+                validation_accuracy: validation.map(|(_, accuracy)| accuracy),
+                learning_rate: self.optimizer.alpha,
+                gradient_norm,
+                time: time(),
+            })?;
+            if validation.is_some_and(|(loss, _)| self.early_stop(&mut run, loss)) {
+                break;
+            }
+            if let Some((path, every)) = &self.autosave {
+                if *every > 0 && self.epoch.is_multiple_of(*every) {
+                    run.elapsed = time();
+                    self.save_run(path, Some(&run))?;
+                }
+            }
+        }
+        // the epoch counter keeps counting every epoch that was run, only the weights go back
+        if let (Some((loss, layers)), Some(epoch)) = (run.best, run.best_epoch) {
+            if self.verbose {
+                println!(
+                    "Restoring weights from epoch {} with validation loss {}",
+                    epoch, loss
+                );
             }
-            // reset accuracy for next epoch
-            self.accuracy = Array2::<f32>::zeros((10, 2));
+            self.network.load_checkpoint(&layers);
+            self.best_epoch = Some(epoch);
         }
+        Ok(())
     }
 
-    // test network on separate data to cross-validate
-    pub fn test(&mut self) {
-        println!("\n\nTESTING NETWORK");
-        self.accuracy = Array2::<f32>::zeros((10, 2));
-        for _ in 0..(self.dataset.testing_data.layer.ncols() / self.dataset.slice_range as usize) {
-            // set slice from testing net
-            self.dataset.set_slice(CONFIG::TEST);
-            // forward
-            self.forward_prop(CONFIG::TEST);
-            // tally accuracy for batch
-            self.set_accuracy(
-                self.get_predictions(),
-                self.dataset.test_label_slice.layer.clone(),
+    // write a row to the training log, if there is one
+    fn log(&mut self, entry: LogEntry) -> Result<()> {
+        match &mut self.log {
+            Some(log) => log.write(&entry),
+            None => Ok(()),
+        }
+    }
+
+    // evaluate the validation set and feed its loss to the scheduler
+    // returns the validation loss and accuracy
+    fn validate(&mut self) -> (f32, f32) {
+        self.evaluate(CONFIG::VALIDATE);
+        let (loss, accuracy) = (self.get_loss(), self.metrics.accuracy());
+        if self.verbose {
+            println!(
+                "Epoch {}: validation accuracy {}, validation loss {}, learning rate {}",
+                self.epoch, accuracy, loss, self.optimizer.alpha
             );
         }
-        // print testing accuracy
-        print!("Accuracy: {}", self.get_accuracy(),);
+        self.reset_metrics();
+        self.scheduler.observe(loss);
+        (loss, accuracy)
+    }
+
+    // keep the weights of the best validation loss so far
+    // returns whether patience has run out
+    fn early_stop(&mut self, run: &mut TrainingRun, loss: f32) -> bool {
+        let patience = self.hyperparameters.patience;
+        if patience == 0 {
+            return false;
+        }
+        if run
+            .best
+            .as_ref()
+            .is_none_or(|(best_loss, _)| loss < *best_loss)
+        {
+            run.best = Some((loss, self.network.checkpoint()));
+            run.best_epoch = Some(self.epoch);
+            run.stale = 0;
+        } else {
+            run.stale += 1;
+            if run.stale >= patience {
+                if self.verbose {
+                    println!(
+                        "Stopping early, validation loss has not improved for {} validations",
+                        run.stale
+                    );
+                }
+                return true;
+            }
+        }
+        false
+    }
+
+    // run every sample of a set through the network once, tallying loss and metrics
+    // the mean loss is available from get_loss until the next epoch or evaluation
+    // layers run in the mode of the set, so dropout is only active for the training set
+    pub fn evaluate(&mut self, mode: CONFIG) -> &Metrics {
+        self.network.set_mode(mode);
+        self.reset_metrics();
+        let batches = self.dataset.batches(
+            mode,
+            self.hyperparameters.batch_size as usize,
+            &mut self.rng,
+        );
+        for indices in batches {
+            let (data, labels) = self.dataset.batch(mode, &indices);
+            // forward
+            self.network.forward_prop(&data);
+            // tally loss and accuracy for batch
+            self.set_loss(&labels.layer);
+            self.set_metrics(&labels.layer);
+        }
+        &self.metrics
+    }
+
+    // test network on separate data to cross-validate, using every testing sample once
+    // returns the metrics of the test set for exporting
+    pub fn test(&mut self) -> &Metrics {
+        println!("\n\nTESTING NETWORK");
+        self.evaluate(CONFIG::TEST);
+        // print per class report, testing accuracy and loss
+        print!("{}", self.metrics);
+        println!("Accuracy: {}", self.metrics.accuracy());
+        println!("Loss: {}", self.get_loss());
+        &self.metrics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray_rand::rand::{rngs::StdRng, Rng};
+    use std::fs;
+
+    // write a tiny random mnist-shaped dataset of 4x4 images to a fresh directory
+    fn write_dataset(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rustnist-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        for (prefix, count) in [("train", 40u32), ("t10k", 10)] {
+            let mut images = [0x803u32, count, 4, 4]
+                .iter()
+                .flat_map(|value| value.to_be_bytes())
+                .collect::<Vec<u8>>();
+            images.extend((0..count * 16).map(|_| rng.gen::<u8>()));
+            fs::write(dir.join(format!("{}-images-idx3-ubyte", prefix)), images).unwrap();
+            let mut labels = [0x801u32, count]
+                .iter()
+                .flat_map(|value| value.to_be_bytes())
+                .collect::<Vec<u8>>();
+            labels.extend((0..count).map(|_| rng.gen_range(0..10u8)));
+            fs::write(dir.join(format!("{}-labels-idx1-ubyte", prefix)), labels).unwrap();
+        }
+        dir
+    }
+
+    fn hyperparameters() -> Hyperparameters {
+        Hyperparameters {
+            layers: vec![8],
+            activation: Activation::PReLU,
+            seed: Some(3),
+            dropout: 0.2,
+            batch_size: 6,
+            optimizer: OptimizerKind::Adam,
+            validation_split: 0.25,
+            scheduler: SchedulerKind::Plateau,
+            plateau_patience: 0,
+            patience: 2,
+            ..Hyperparameters::default()
+        }
+    }
+
+    #[test]
+    fn initializers_are_picked_per_layer_and_saved_in_checkpoints() {
+        let dir = write_dataset("initializers");
+        let initializers = |given: Vec<Initializer>| {
+            let hyperparameters = Hyperparameters {
+                layers: vec![8, 6],
+                initializers: given,
+                ..hyperparameters()
+            };
+            Model::new(hyperparameters, &dir).map(|model| {
+                // rebuilding the network from its checkpoint keeps the initializers
+                let network = Sequential::from_checkpoint(&model.network.checkpoint()).unwrap();
+                network
+                    .checkpoint()
+                    .iter()
+                    .filter_map(|layer| layer.spec.initializer())
+                    .collect::<Vec<_>>()
+            })
+        };
+        use Initializer::*;
+        assert_eq!(
+            initializers(Vec::new()).unwrap(),
+            vec![HeNormal, HeNormal, XavierNormal]
+        );
+        assert_eq!(
+            initializers(vec![LecunUniform]).unwrap(),
+            vec![LecunUniform, LecunUniform, XavierNormal]
+        );
+        assert_eq!(
+            initializers(vec![Zeros, Orthogonal, XavierUniform]).unwrap(),
+            vec![Zeros, Orthogonal, XavierUniform]
+        );
+        assert!(initializers(vec![Zeros, Orthogonal]).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn last_batch_of_an_epoch_uses_the_rate_of_that_epoch() {
+        let dir = write_dataset("schedule");
+        let mut model = Model::new(
+            Hyperparameters {
+                scheduler: SchedulerKind::Step,
+                step_size: 1,
+                gamma: 0.5,
+                ..hyperparameters()
+            },
+            &dir,
+        )
+        .unwrap();
+        model.set_verbose(false);
+        model.train(1).unwrap();
+        assert_eq!(model.optimizer.alpha, model.hyperparameters.alpha);
+        model.train(1).unwrap();
+        assert_eq!(model.optimizer.alpha, model.hyperparameters.alpha * 0.5);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn early_stopping_restores_weights_but_keeps_counting_epochs() {
+        let dir = write_dataset("early-stopping");
+        let mut model = Model::new(hyperparameters(), &dir).unwrap();
+        model.set_verbose(false);
+        model.train(50).unwrap();
+        // patience runs out 2 validations after the best one, which is what gets restored
+        let best = model.best_epoch().unwrap();
+        assert!(model.epoch() < 50);
+        assert_eq!(model.epoch(), best + 2);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn resumed_training_matches_uninterrupted_training() {
+        let dir = write_dataset("resume");
+        let mut uninterrupted = Model::new(hyperparameters(), &dir).unwrap();
+        uninterrupted.train(4).unwrap();
+
+        // interrupted by another thread as soon as the run clears the flag on starting, then
+        // saved every 2 epochs once resumed
+        let path = dir.join("checkpoint.json");
+        let mut interrupted = (0..100)
+            .map(|_| {
+                let mut model = Model::new(hyperparameters(), &dir).unwrap();
+                model.autosave(path.clone(), 2);
+                let flag = model.interrupt_flag();
+                flag.store(true, Ordering::SeqCst);
+                let interrupter = std::thread::spawn(move || {
+                    while flag.load(Ordering::SeqCst) {
+                        std::hint::spin_loop();
+                    }
+                    flag.store(true, Ordering::SeqCst);
+                });
+                model.train(4).unwrap();
+                interrupter.join().unwrap();
+                model
+            })
+            // the thread can be too late to stop the short run, though that hardly ever happens
+            .find(|model| model.unfinished())
+            .unwrap();
+        assert!(interrupted.epoch() < 4);
+
+        let mut resumed = Model::load(&path, &dir).unwrap();
+        resumed.autosave(path.clone(), 2);
+        resumed.resume().unwrap();
+        assert!(!resumed.unfinished());
+        // the last checkpoint saved while resuming finishes the run the same way again
+        let mut finished = Model::load(&path, &dir).unwrap();
+        assert!(finished.unfinished());
+        finished.resume().unwrap();
+        // the interrupted model resumes too, even though its interrupt flag is still set
+        assert!(interrupted.interrupt_flag().load(Ordering::SeqCst));
+        interrupted.resume().unwrap();
+        assert!(!interrupted.unfinished());
+
+        for model in [&resumed, &finished, &interrupted] {
+            assert_eq!(model.epoch(), uninterrupted.epoch());
+            for (layer, expected) in model
+                .network
+                .checkpoint()
+                .iter()
+                .zip(uninterrupted.network.checkpoint())
+            {
+                assert_eq!(layer.weights, expected.weights);
+                assert_eq!(layer.biases, expected.biases);
+                assert_eq!(layer.buffers, expected.buffers);
+            }
+        }
+        assert!(resumed.resume().is_err());
+        fs::remove_dir_all(dir).unwrap();
     }
 }
@@ -0,0 +1,278 @@
+use clap::ValueEnum;
+use ndarray::{prelude::Array2, Zip};
+use serde::{Deserialize, Serialize};
+
+// added to denominators so that parameters with no gradient history are not divided by zero
+const EPSILON: f32 = 1e-8;
+
+// update rules available for updating weights and biases
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum OptimizerKind {
+    // plain stochastic gradient descent
+    #[default]
+    Sgd,
+    // sgd with a velocity accumulated from previous gradients
+    Momentum,
+    // momentum, with the gradient evaluated after the momentum step
+    Nesterov,
+    // sgd scaled by a running average of squared gradients
+    #[clap(name = "rmsprop")]
+    RMSProp,
+    // bias corrected running averages of gradients and squared gradients
+    Adam,
+    // adam with weight decay applied directly to the parameters instead of the gradient
+    #[clap(name = "adamw")]
+    AdamW,
+}
+
+// running averages kept for a single parameter array, the same shape as the parameter
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OptimizerState {
+    // velocity for momentum and nesterov, mean of gradients for adam
+    pub first_moment: Array2<f32>,
+    // mean of squared gradients for rmsprop and adam
+    pub second_moment: Array2<f32>,
+}
+
+impl OptimizerState {
+    // create empty state, resized to the parameter on its first update
+    pub fn new() -> OptimizerState {
+        OptimizerState {
+            first_moment: Array2::<f32>::zeros((0, 0)),
+            second_moment: Array2::<f32>::zeros((0, 0)),
+        }
+    }
+
+    // make sure the state matches the shape of the parameter, starting over if it does not
+    fn fit(&mut self, parameter: &Array2<f32>) {
+        if self.first_moment.raw_dim() != parameter.raw_dim() {
+            self.first_moment = Array2::<f32>::zeros(parameter.raw_dim());
+            self.second_moment = Array2::<f32>::zeros(parameter.raw_dim());
+        }
+    }
+}
+
+impl Default for OptimizerState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// optimizer shared by every layer of a network, each parameter keeps its own OptimizerState
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Optimizer {
+    pub kind: OptimizerKind,
+    // learning rate of network
+    pub alpha: f32,
+    // decay of the first moment, used as the momentum coefficient and as adam's beta1
+    pub momentum: f32,
+    // decay of the second moment, used by rmsprop and adam
+    pub beta2: f32,
+    // l2 penalty on the parameters, decoupled from the gradient for adamw
+    pub weight_decay: f32,
+    // amount of updates made so far, used for adam's bias correction
+    step: usize,
+}
+
+impl Optimizer {
+    pub fn new(
+        kind: OptimizerKind,
+        alpha: f32,
+        momentum: f32,
+        beta2: f32,
+        weight_decay: f32,
+    ) -> Optimizer {
+        Optimizer {
+            kind,
+            alpha,
+            momentum,
+            beta2,
+            weight_decay,
+            step: 0,
+        }
+    }
+
+    // advance to the next update, must be called once before every batch of updates
+    pub fn next_step(&mut self) {
+        self.step += 1;
+    }
+
+    // amount of updates made so far
+    pub fn step(&self) -> usize {
+        self.step
+    }
+
+    // update a single parameter in place using its gradient and state
+    pub fn update(
+        &self,
+        parameter: &mut Array2<f32>,
+        gradient: &Array2<f32>,
+        state: &mut OptimizerState,
+    ) {
+        state.fit(parameter);
+        let alpha = self.alpha;
+        let momentum = self.momentum;
+        let beta2 = self.beta2;
+        // coupled weight decay adds the l2 penalty's derivative to the gradient
+        let decay = match self.kind {
+            OptimizerKind::AdamW => 0f32,
+            _ => self.weight_decay,
+        };
+        let zip = Zip::from(parameter)
+            .and(gradient)
+            .and(&mut state.first_moment)
+            .and(&mut state.second_moment);
+        match self.kind {
+            OptimizerKind::Sgd => zip.for_each(|p, g, _, _| *p -= alpha * (g + decay * *p)),
+            OptimizerKind::Momentum => zip.for_each(|p, g, v, _| {
+                *v = momentum * *v + g + decay * *p;
+                *p -= alpha * *v;
+            }),
+            OptimizerKind::Nesterov => zip.for_each(|p, g, v, _| {
+                let g = g + decay * *p;
+                *v = momentum * *v + g;
+                *p -= alpha * (g + momentum * *v);
+            }),
+            OptimizerKind::RMSProp => zip.for_each(|p, g, _, s| {
+                let g = g + decay * *p;
+                *s = beta2 * *s + (1f32 - beta2) * g * g;
+                *p -= alpha * g / (s.sqrt() + EPSILON);
+            }),
+            OptimizerKind::Adam | OptimizerKind::AdamW => {
+                // bias correction for the moments starting at zero
+                let step = self.step.max(1) as i32;
+                let correction1 = 1f32 - momentum.powi(step);
+                let correction2 = 1f32 - beta2.powi(step);
+                let decoupled = match self.kind {
+                    OptimizerKind::AdamW => self.weight_decay,
+                    _ => 0f32,
+                };
+                zip.for_each(|p, g, m, v| {
+                    let g = g + decay * *p;
+                    *m = momentum * *m + (1f32 - momentum) * g;
+                    *v = beta2 * *v + (1f32 - beta2) * g * g;
+                    let m_hat = *m / correction1;
+                    let v_hat = *v / correction2;
+                    *p -= alpha * (m_hat / (v_hat.sqrt() + EPSILON) + decoupled * *p);
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::arr2;
+
+    // optimizer with a learning rate of 0.1, momentum 0.9, beta2 0.99 and weight decay 0.1
+    fn optimizer(kind: OptimizerKind) -> Optimizer {
+        let mut optimizer = Optimizer::new(kind, 0.1, 0.9, 0.99, 0.1);
+        optimizer.next_step();
+        optimizer
+    }
+
+    // update a parameter of 1 with a gradient of 0.5, starting from the given moments
+    fn update(optimizer: &Optimizer, first_moment: f32, second_moment: f32) -> (f32, f32, f32) {
+        let mut parameter = arr2(&[[1f32]]);
+        let mut state = OptimizerState {
+            first_moment: arr2(&[[first_moment]]),
+            second_moment: arr2(&[[second_moment]]),
+        };
+        optimizer.update(&mut parameter, &arr2(&[[0.5]]), &mut state);
+        (
+            parameter[[0, 0]],
+            state.first_moment[[0, 0]],
+            state.second_moment[[0, 0]],
+        )
+    }
+
+    fn assert_close(found: (f32, f32, f32), expected: (f32, f32, f32)) {
+        for (found_value, expected_value) in [
+            (found.0, expected.0),
+            (found.1, expected.1),
+            (found.2, expected.2),
+        ] {
+            assert!(
+                (found_value - expected_value).abs() < 1e-6,
+                "{:?} is not {:?}",
+                found,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn sgd_step() {
+        // 1 - 0.1 * (0.5 + 0.1 * 1)
+        assert_close(
+            update(&optimizer(OptimizerKind::Sgd), 0.0, 0.0),
+            (0.94, 0.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn momentum_step() {
+        // v = 0.9 * 0.2 + 0.5 + 0.1 * 1 = 0.78, p = 1 - 0.1 * 0.78
+        assert_close(
+            update(&optimizer(OptimizerKind::Momentum), 0.2, 0.0),
+            (0.922, 0.78, 0.0),
+        );
+    }
+
+    #[test]
+    fn nesterov_step() {
+        // g = 0.6, v = 0.9 * 0.2 + 0.6 = 0.78, p = 1 - 0.1 * (0.6 + 0.9 * 0.78)
+        assert_close(
+            update(&optimizer(OptimizerKind::Nesterov), 0.2, 0.0),
+            (0.8698, 0.78, 0.0),
+        );
+    }
+
+    #[test]
+    fn rmsprop_step() {
+        // g = 0.6, s = 0.99 * 1 + 0.01 * 0.36 = 0.9936, p = 1 - 0.1 * 0.6 / sqrt(0.9936)
+        assert_close(
+            update(&optimizer(OptimizerKind::RMSProp), 0.0, 1.0),
+            (0.939_807, 0.0, 0.9936),
+        );
+    }
+
+    #[test]
+    fn adam_corrects_the_bias_of_its_moments() {
+        // the first step moves by exactly the learning rate, since the corrected moments are
+        // g and g * g for the decayed gradient g = 0.6
+        let mut adam = optimizer(OptimizerKind::Adam);
+        let mut parameter = arr2(&[[1f32]]);
+        let mut state = OptimizerState::new();
+        adam.update(&mut parameter, &arr2(&[[0.5]]), &mut state);
+        assert_close(
+            (
+                parameter[[0, 0]],
+                state.first_moment[[0, 0]],
+                state.second_moment[[0, 0]],
+            ),
+            (0.9, 0.06, 0.0036),
+        );
+        // second step, g = 0.5 + 0.1 * 0.9 = 0.59, corrected by 1 - 0.9^2 and 1 - 0.99^2
+        adam.next_step();
+        adam.update(&mut parameter, &arr2(&[[0.5]]), &mut state);
+        assert_close(
+            (
+                parameter[[0, 0]],
+                state.first_moment[[0, 0]],
+                state.second_moment[[0, 0]],
+            ),
+            (0.800_043_5, 0.113, 0.007_045),
+        );
+    }
+
+    #[test]
+    fn adamw_decays_the_parameter_outside_the_moments() {
+        // the moments only see the gradient 0.5, the decay of 0.1 * 1 is added to the step
+        assert_close(
+            update(&optimizer(OptimizerKind::AdamW), 0.0, 0.0),
+            (0.89, 0.05, 0.0025),
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use std::path::{Path, PathBuf};
+
+use ndarray::{prelude::Array2, Axis};
+
+use crate::checkpoint::Checkpoint;
+use crate::error::{Error, Result};
+use crate::image::{self, IMAGE_SIZE};
+use crate::layers::dataset::DatasetKind;
+use crate::layers::layer::Layer;
+use crate::model::CONFIG;
+use crate::sequential::Sequential;
+
+// most probable class of one sample, along with the probability of every class
+#[derive(Clone, Debug, PartialEq)]
+pub struct Prediction {
+    pub class: usize,
+    pub probabilities: Vec<f32>,
+}
+
+// load only the network of a checkpoint file and which dataset it was trained on, for inference
+// without the dataset
+pub fn load_network(path: &Path) -> Result<(Sequential, DatasetKind)> {
+    let checkpoint = Checkpoint::load(path)?;
+    let network = Sequential::from_checkpoint(&checkpoint.layers)?;
+    Ok((network, checkpoint.hyperparameters.dataset))
+}
+
+// predict the class of every sample of a batch, one column per sample
+// the network is switched to evaluation, so dropout leaves the predictions alone
+pub fn predict(network: &mut Sequential, data: &Array2<f32>) -> Result<Vec<Prediction>> {
+    let input_size = network.input_size().ok_or_else(no_layers)?;
+    if data.nrows() != input_size {
+        return Err(Error::ShapeMismatch(format!(
+            "network takes {} inputs, but the samples have {}",
+            input_size,
+            data.nrows()
+        )));
+    }
+    network.set_mode(CONFIG::TEST);
+    network.forward_prop(&Layer::dummy_layer(data.clone()));
+    let output = network.output().ok_or_else(no_layers)?;
+    Ok(output
+        .layer
+        .axis_iter(Axis(1))
+        .map(|probabilities| {
+            let mut class = 0;
+            for (i, probability) in probabilities.iter().enumerate() {
+                if *probability > probabilities[class] {
+                    class = i;
+                }
+            }
+            Prediction {
+                class,
+                probabilities: probabilities.to_vec(),
+            }
+        })
+        .collect())
+}
+
+fn no_layers() -> Error {
+    Error::ShapeMismatch("network has no layers".to_string())
+}
+
+// predict the class of each png or pgm image, processed the same way as the images of the
+// dataset the network was trained on
+pub fn predict_images(
+    network: &mut Sequential,
+    dataset: DatasetKind,
+    files: &[PathBuf],
+) -> Result<Vec<Prediction>> {
+    let pixels = IMAGE_SIZE * IMAGE_SIZE;
+    let input_size = network.input_size().ok_or_else(no_layers)?;
+    if input_size != pixels {
+        return Err(Error::ShapeMismatch(format!(
+            "model takes {} inputs, but images are converted to {}x{} pixels",
+            input_size, IMAGE_SIZE, IMAGE_SIZE
+        )));
+    }
+    let output_size = network.output_size().ok_or_else(no_layers)?;
+    if output_size != dataset.classes() {
+        return Err(Error::ShapeMismatch(format!(
+            "model has {} outputs, but {:?} has {} classes",
+            output_size,
+            dataset,
+            dataset.classes()
+        )));
+    }
+    // every image becomes a column of a single batch
+    let mut images = Array2::<f32>::zeros((pixels, files.len()));
+    for (j, file) in files.iter().enumerate() {
+        images.column_mut(j).assign(&image::to_column(
+            &image::read_image(file)?,
+            dataset.framing(),
+        ));
+    }
+    predict(network, &images)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layers::{initializer::Initializer, softmax::Softmax};
+    use ndarray_rand::rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn predicts_a_class_for_every_sample() {
+        let mut network = Sequential::new();
+        let mut rng = StdRng::seed_from_u64(0);
+        network
+            .add(Box::new(
+                Softmax::new(4, 3, Initializer::XavierNormal, &mut rng).unwrap(),
+            ))
+            .unwrap();
+        let predictions = predict(&mut network, &Array2::ones((4, 2))).unwrap();
+        assert_eq!(predictions.len(), 2);
+        for prediction in predictions {
+            assert_eq!(prediction.probabilities.len(), 3);
+            assert!(
+                prediction.probabilities[prediction.class]
+                    >= *prediction
+                        .probabilities
+                        .iter()
+                        .max_by(|a, b| a.total_cmp(b))
+                        .unwrap()
+            );
+        }
+        assert!(matches!(
+            predict(&mut network, &Array2::ones((5, 2))),
+            Err(Error::ShapeMismatch(_))
+        ));
+        assert!(matches!(
+            predict(&mut Sequential::new(), &Array2::ones((4, 2))),
+            Err(Error::ShapeMismatch(_))
+        ));
+    }
+}
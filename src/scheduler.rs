@@ -0,0 +1,202 @@
+use std::f32::consts::PI;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+// schedules for changing the learning rate over the course of training
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SchedulerKind {
+    // keep the learning rate fixed
+    #[default]
+    Constant,
+    // multiply the learning rate by gamma every step_size epochs
+    Step,
+    // multiply the learning rate by gamma every epoch
+    Exponential,
+    // anneal from the learning rate down to min_alpha along a cosine, restarting every step_size
+    // epochs
+    Cosine,
+    // multiply the learning rate by gamma once the validation loss stops improving
+    Plateau,
+}
+
+// learning rate scheduler consulted by the model before every update
+// every schedule can be combined with a linear warmup over the first epochs
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Scheduler {
+    pub kind: SchedulerKind,
+    // learning rate the schedule starts from
+    alpha: f32,
+    // epochs between decays for step, length of each cycle for cosine
+    step_size: usize,
+    // factor the learning rate is decayed by for step, exponential and plateau
+    gamma: f32,
+    // lowest learning rate any schedule decays to
+    min_alpha: f32,
+    // epochs the learning rate is linearly increased from 0 over
+    warmup: usize,
+    // validations without a lower loss before the plateau schedule decays
+    plateau_patience: usize,
+    // lowest validation loss seen by the plateau schedule, and validations since then
+    best_loss: Option<f32>,
+    stale: usize,
+    // decay applied so far by the plateau schedule
+    plateau_factor: f32,
+}
+
+impl Scheduler {
+    pub fn new(
+        kind: SchedulerKind,
+        alpha: f32,
+        step_size: usize,
+        gamma: f32,
+        min_alpha: f32,
+        warmup: usize,
+        plateau_patience: usize,
+    ) -> Scheduler {
+        Scheduler {
+            kind,
+            alpha,
+            step_size: step_size.max(1),
+            gamma,
+            min_alpha,
+            warmup,
+            plateau_patience,
+            best_loss: None,
+            stale: 0,
+            plateau_factor: 1f32,
+        }
+    }
+
+    // learning rate at a point in training
+    // progress: epochs trained so far, including the fraction of the current epoch
+    pub fn rate(&self, progress: f32) -> f32 {
+        let epoch = progress.floor();
+        let rate = match self.kind {
+            SchedulerKind::Constant => self.alpha,
+            SchedulerKind::Step => {
+                self.alpha * self.gamma.powf((epoch / self.step_size as f32).floor())
+            }
+            SchedulerKind::Exponential => self.alpha * self.gamma.powf(epoch),
+            SchedulerKind::Cosine => {
+                let cycle = (progress % self.step_size as f32) / self.step_size as f32;
+                self.min_alpha + (self.alpha - self.min_alpha) * (1f32 + (PI * cycle).cos()) / 2f32
+            }
+            SchedulerKind::Plateau => self.alpha * self.plateau_factor,
+        };
+        // decays stop at min_alpha, but never raise a learning rate that started below it
+        let rate = rate.max(self.min_alpha.min(self.alpha));
+        if progress < self.warmup as f32 {
+            rate * progress / self.warmup as f32
+        } else {
+            rate
+        }
+    }
+
+    // report a validation loss to the plateau schedule, other schedules ignore it
+    pub fn observe(&mut self, loss: f32) {
+        if self.kind != SchedulerKind::Plateau {
+            return;
+        }
+        if self.best_loss.is_none_or(|best_loss| loss < best_loss) {
+            self.best_loss = Some(loss);
+            self.stale = 0;
+        } else {
+            self.stale += 1;
+            if self.stale > self.plateau_patience {
+                self.plateau_factor *= self.gamma;
+                self.stale = 0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_rates(scheduler: &Scheduler, expected: &[(f32, f32)]) {
+        for &(progress, rate) in expected {
+            assert!(
+                (scheduler.rate(progress) - rate).abs() < 1e-6,
+                "rate at {} was {}, expected {}",
+                progress,
+                scheduler.rate(progress),
+                rate
+            );
+        }
+    }
+
+    #[test]
+    fn step_decays_once_every_step_size_epochs() {
+        let scheduler = Scheduler::new(SchedulerKind::Step, 0.1, 2, 0.5, 0.0, 0, 0);
+        assert_rates(
+            &scheduler,
+            &[
+                (0.0, 0.1),
+                (1.0, 0.1),
+                (1.99, 0.1),
+                (2.0, 0.05),
+                (3.99, 0.05),
+                (4.0, 0.025),
+            ],
+        );
+    }
+
+    #[test]
+    fn exponential_decays_at_every_epoch_boundary() {
+        let scheduler = Scheduler::new(SchedulerKind::Exponential, 0.1, 1, 0.5, 0.01, 0, 0);
+        assert_rates(
+            &scheduler,
+            &[
+                (0.0, 0.1),
+                (0.99, 0.1),
+                (1.0, 0.05),
+                (2.0, 0.025),
+                (3.0, 0.0125),
+                (4.0, 0.01),
+            ],
+        );
+    }
+
+    #[test]
+    fn cosine_reaches_its_minimum_only_at_the_end_of_a_cycle() {
+        let scheduler = Scheduler::new(SchedulerKind::Cosine, 0.1, 2, 0.1, 0.02, 0, 0);
+        assert_rates(
+            &scheduler,
+            &[
+                (0.0, 0.1),
+                (1.0, 0.06),
+                (1.5, 0.02 + 0.08 * (1.0 + (0.75 * PI).cos()) / 2.0),
+                (1.99, 0.02 + 0.08 * (1.0 + (0.995 * PI).cos()) / 2.0),
+                // the next cycle restarts from alpha
+                (2.0, 0.1),
+                (3.0, 0.06),
+            ],
+        );
+    }
+
+    #[test]
+    fn warmup_ramps_up_to_the_schedule() {
+        let scheduler = Scheduler::new(SchedulerKind::Step, 0.1, 2, 0.5, 0.0, 4, 0);
+        assert_rates(
+            &scheduler,
+            &[
+                (0.0, 0.0),
+                (1.0, 0.025),
+                (2.0, 0.025),
+                (3.0, 0.0375),
+                (4.0, 0.025),
+            ],
+        );
+    }
+
+    #[test]
+    fn plateau_decays_after_patience_runs_out() {
+        let mut scheduler = Scheduler::new(SchedulerKind::Plateau, 0.1, 1, 0.5, 0.0, 0, 1);
+        for loss in [1.0, 0.9, 0.95, 0.95] {
+            scheduler.observe(loss);
+        }
+        assert_rates(&scheduler, &[(4.0, 0.05)]);
+    }
+}
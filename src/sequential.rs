@@ -0,0 +1,251 @@
+use crate::checkpoint::LayerCheckpoint;
+use crate::error::{Error, Result};
+use crate::layers::layer::{ActivationLayer, Layer};
+use crate::model::CONFIG;
+use crate::optimizer::{Optimizer, OptimizerState};
+use ndarray_rand::rand::Rng;
+
+// stack of layers where each layer feeds directly into the next one
+pub struct Sequential {
+    // layers in order of forward propogation, the last layer is the output layer
+    pub layers: Vec<Box<dyn ActivationLayer>>,
+}
+
+impl Sequential {
+    // create an empty network
+    pub fn new() -> Sequential {
+        Sequential { layers: Vec::new() }
+    }
+
+    // rebuild a network from the layers saved in a checkpoint
+    pub fn from_checkpoint(layers: &[LayerCheckpoint]) -> Result<Sequential> {
+        if layers.is_empty() {
+            return Err(Error::ShapeMismatch(
+                "checkpoint does not hold any layers".to_string(),
+            ));
+        }
+        let mut network = Sequential::new();
+        for (i, checkpoint) in layers.iter().enumerate() {
+            let mut layer = checkpoint.spec.build()?;
+            // parameters and buffers have to match the shapes the described layer was built with
+            let buffers = layer.checkpoint().buffers;
+            if layer.layer().weights.raw_dim() != checkpoint.weights.raw_dim()
+                || layer.layer().biases.raw_dim() != checkpoint.biases.raw_dim()
+                || buffers.len() != checkpoint.buffers.len()
+                || buffers
+                    .iter()
+                    .zip(&checkpoint.buffers)
+                    .any(|(built, saved)| built.raw_dim() != saved.raw_dim())
+            {
+                return Err(Error::ShapeMismatch(format!(
+                    "parameters of layer {} do not match its description {:?}",
+                    i, checkpoint.spec
+                )));
+            }
+            layer.load_checkpoint(checkpoint);
+            network.add(layer)?;
+        }
+        Ok(network)
+    }
+
+    // description and parameters of every layer, in order
+    pub fn checkpoint(&self) -> Vec<LayerCheckpoint> {
+        self.layers.iter().map(|layer| layer.checkpoint()).collect()
+    }
+
+    // overwrite the parameters of every layer with ones saved by checkpoint, keeping the layers
+    // themselves and their optimizer state
+    pub fn load_checkpoint(&mut self, layers: &[LayerCheckpoint]) {
+        for (layer, checkpoint) in self.layers.iter_mut().zip(layers) {
+            layer.load_checkpoint(checkpoint);
+        }
+    }
+
+    // copy out the optimizer state of every layer, for resuming training
+    pub fn optimizer_states(&self) -> Vec<Vec<OptimizerState>> {
+        self.layers
+            .iter()
+            .map(|layer| layer.optimizer_states())
+            .collect()
+    }
+
+    // restore optimizer state copied out by optimizer_states
+    pub fn load_optimizer_states(&mut self, states: &[Vec<OptimizerState>]) {
+        for (layer, states) in self.layers.iter_mut().zip(states) {
+            layer.load_optimizer_states(states);
+        }
+    }
+
+    // add a layer to the end of the network, which has to take the outputs of the layer before
+    // it as input
+    pub fn add(&mut self, layer: Box<dyn ActivationLayer>) -> Result<()> {
+        if let Some(previous_layer) = self.layers.last() {
+            Sequential::check_sizes(self.layers.len(), previous_layer.as_ref(), layer.as_ref())?;
+        }
+        self.layers.push(layer);
+        Ok(())
+    }
+
+    // make sure every layer takes the outputs of the layer before it as input, for networks
+    // whose layers were put together without add
+    pub fn validate(&self) -> Result<()> {
+        for (i, pair) in self.layers.windows(2).enumerate() {
+            Sequential::check_sizes(i + 1, pair[0].as_ref(), pair[1].as_ref())?;
+        }
+        Ok(())
+    }
+
+    fn check_sizes(
+        index: usize,
+        previous_layer: &dyn ActivationLayer,
+        layer: &dyn ActivationLayer,
+    ) -> Result<()> {
+        let outputs = previous_layer.layer().layer.nrows();
+        let inputs = layer.spec().input_size();
+        if inputs != outputs {
+            return Err(Error::ShapeMismatch(format!(
+                "layer {} takes {} inputs, but the layer before it has {} outputs",
+                index, inputs, outputs
+            )));
+        }
+        Ok(())
+    }
+
+    // amount of inputs each sample needs to have
+    pub fn input_size(&self) -> Option<usize> {
+        self.layers.first().map(|layer| layer.spec().input_size())
+    }
+
+    // amount of outputs each sample gets, the nodes of the output layer
+    pub fn output_size(&self) -> Option<usize> {
+        self.output().map(|output| output.layer.nrows())
+    }
+
+    // output layer of the network
+    pub fn output(&self) -> Option<&Layer> {
+        self.layers.last().map(|layer| layer.layer())
+    }
+
+    // forward propogation through every layer, starting from the input data
+    pub fn forward_prop(&mut self, input: &Layer) {
+        for i in 0..self.layers.len() {
+            let (before, after) = self.layers.split_at_mut(i);
+            match before.last() {
+                Some(previous_layer) => after[0].forward_prop(previous_layer.layer()),
+                None => after[0].forward_prop(input),
+            }
+        }
+    }
+
+    // backwards propogation through every layer, starting from the gradient of the loss
+    // output_gradient: gradient of the loss with respect to the output of the network
+    // input: data that was used in the last forward pass
+    pub fn backward_prop(&mut self, output_gradient: &Layer, input: &Layer) {
+        self.backward_prop_from(output_gradient, input, false);
+    }
+
+    // backwards propogation starting from the gradient of the loss with respect to the
+    // preactivation of the output layer, see ActivationLayer::backward_prop_preactivation
+    pub fn backward_prop_preactivation(&mut self, gradient: &Layer, input: &Layer) {
+        self.backward_prop_from(gradient, input, true);
+    }
+
+    fn backward_prop_from(&mut self, output_gradient: &Layer, input: &Layer, preactivation: bool) {
+        let mut gradient = output_gradient.clone();
+        let output = self.layers.len().saturating_sub(1);
+        for i in (0..self.layers.len()).rev() {
+            let (before, after) = self.layers.split_at_mut(i);
+            let next_layer = before.last().map_or(input, |layer| layer.layer());
+            if preactivation && i == output {
+                after[0].backward_prop_preactivation(&gradient, next_layer);
+            } else {
+                after[0].backward_prop(&gradient, next_layer);
+            }
+            // the input layer has nothing to pass its gradient to
+            if i > 0 {
+                gradient = Layer::dummy_layer(after[0].input_gradient());
+            }
+        }
+    }
+
+    // switch every layer between training and evaluating
+    pub fn set_mode(&mut self, mode: CONFIG) {
+        for layer in self.layers.iter_mut() {
+            layer.set_mode(mode);
+        }
+    }
+
+    // reseed every layer that draws random numbers while training with a seed drawn from rng
+    pub fn reseed<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        for layer in self.layers.iter_mut() {
+            layer.reseed(rng.gen());
+        }
+    }
+
+    // l2 norm of the gradients of every parameter the optimizer updates, from the last backward
+    // prop
+    pub fn gradient_norm(&mut self) -> f32 {
+        self.layers
+            .iter_mut()
+            .flat_map(|layer| layer.parameters())
+            .map(|parameter| parameter.gradient.iter().map(|x| x * x).sum::<f32>())
+            .sum::<f32>()
+            .sqrt()
+    }
+
+    // updating of weights and biases in every layer
+    pub fn update_params(&mut self, optimizer: &Optimizer) {
+        for layer in self.layers.iter_mut() {
+            layer.update_params(optimizer);
+        }
+    }
+}
+
+impl Default for Sequential {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layers::{initializer::Initializer, relu::ReLU, softmax::Softmax};
+    use ndarray_rand::rand::{rngs::StdRng, SeedableRng};
+
+    // a hidden layer of 10 nodes followed by an output layer expecting 20 inputs
+    fn mismatched_layers() -> (Box<dyn ActivationLayer>, Box<dyn ActivationLayer>) {
+        let mut rng = StdRng::seed_from_u64(0);
+        let hidden = ReLU::new(4, 10, 0.0, Initializer::HeNormal, &mut rng).unwrap();
+        let output = Softmax::new(20, 3, Initializer::XavierNormal, &mut rng).unwrap();
+        (Box::new(hidden), Box::new(output))
+    }
+
+    #[test]
+    fn add_rejects_mismatched_inner_layer() {
+        let (hidden, output) = mismatched_layers();
+        let mut network = Sequential::new();
+        network.add(hidden).unwrap();
+        assert!(matches!(network.add(output), Err(Error::ShapeMismatch(_))));
+        assert_eq!(network.layers.len(), 1);
+    }
+
+    #[test]
+    fn validate_rejects_mismatched_inner_layer() {
+        let (hidden, output) = mismatched_layers();
+        let network = Sequential {
+            layers: vec![hidden, output],
+        };
+        assert!(matches!(network.validate(), Err(Error::ShapeMismatch(_))));
+    }
+
+    #[test]
+    fn from_checkpoint_rejects_mismatched_inner_layer() {
+        let (hidden, output) = mismatched_layers();
+        let checkpoint = vec![hidden.checkpoint(), output.checkpoint()];
+        assert!(matches!(
+            Sequential::from_checkpoint(&checkpoint),
+            Err(Error::ShapeMismatch(_))
+        ));
+    }
+}
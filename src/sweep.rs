@@ -0,0 +1,429 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+
+use clap::ValueEnum;
+use ndarray_rand::rand::{self, seq::SliceRandom, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::layers::dataset::Dataset;
+use crate::metrics::ReportFormat;
+use crate::model::{Hyperparameters, Model, CONFIG};
+
+// ways of picking the configurations a sweep trains
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Search {
+    // every combination of the values given for each hyperparameter
+    #[default]
+    Grid,
+    // trials of those combinations, picked at random
+    Random,
+}
+
+// values a sweep tries for each hyperparameter, read from json or given on the command line
+// a list left empty keeps the value of the base hyperparameters
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct SweepSpec {
+    pub search: Search,
+    // amount of combinations random search trains
+    pub trials: usize,
+    // sizes of the hidden layers, in order
+    pub layers: Vec<Vec<usize>>,
+    pub alpha: Vec<f32>,
+    pub batch_size: Vec<isize>,
+    pub epochs: Vec<usize>,
+}
+
+impl Default for SweepSpec {
+    fn default() -> Self {
+        SweepSpec {
+            search: Search::Grid,
+            trials: 10,
+            layers: Vec::new(),
+            alpha: Vec::new(),
+            batch_size: Vec::new(),
+            epochs: Vec::new(),
+        }
+    }
+}
+
+// a single configuration of a sweep
+#[derive(Serialize, Clone, Debug)]
+pub struct Trial {
+    pub hyperparameters: Hyperparameters,
+    // epochs the configuration is trained for
+    pub epochs: usize,
+}
+
+// how a configuration did on the validation set after training, or why it could not be trained
+#[derive(Serialize, Clone, Debug)]
+pub struct TrialResult {
+    pub trial: Trial,
+    pub validation_accuracy: Option<f32>,
+    pub validation_loss: Option<f32>,
+    pub error: Option<String>,
+    // wall time of building, training and validating the model
+    pub seconds: f64,
+}
+
+// results of every trial of a sweep, best first
+pub struct Sweep {
+    pub results: Vec<TrialResult>,
+}
+
+impl SweepSpec {
+    // read a spec from a json file
+    pub fn load(path: &Path) -> Result<SweepSpec> {
+        let contents = fs::read_to_string(path).map_err(|err| Error::io(path, err))?;
+        serde_json::from_str(&contents)
+            .map_err(|err| Error::invalid_file(path, format!("not a valid sweep spec: {}", err)))
+    }
+
+    // configurations to train, each taking the values of base that are not swept
+    // every trial shares one seed, picked at random when base has none, so they only differ in
+    // the swept values and random search picks the same trials for the same seed
+    // epochs: epochs to train for when they are not swept
+    pub fn trials(&self, base: &Hyperparameters, epochs: usize) -> Result<Vec<Trial>> {
+        if base.validation_split <= 0.0 {
+            return Err(Error::InvalidConfig(
+                "a sweep ranks configurations by validation accuracy, so it needs a validation split"
+                    .to_string(),
+            ));
+        }
+        if self.search == Search::Random && self.trials == 0 {
+            return Err(Error::InvalidConfig(
+                "random search needs at least one trial".to_string(),
+            ));
+        }
+        let seed = base.seed.unwrap_or_else(rand::random);
+        let layers = or_base(&self.layers, base.layers.clone());
+        let alphas = or_base(&self.alpha, base.alpha);
+        let batch_sizes = or_base(&self.batch_size, base.batch_size);
+        let epoch_counts = or_base(&self.epochs, epochs);
+        let mut trials = Vec::new();
+        for layers in &layers {
+            for &alpha in &alphas {
+                for &batch_size in &batch_sizes {
+                    for &epochs in &epoch_counts {
+                        trials.push(Trial {
+                            hyperparameters: Hyperparameters {
+                                layers: layers.clone(),
+                                alpha,
+                                batch_size,
+                                seed: Some(seed),
+                                ..base.clone()
+                            },
+                            epochs,
+                        });
+                    }
+                }
+            }
+        }
+        if self.search == Search::Random {
+            trials.shuffle(&mut ChaCha8Rng::seed_from_u64(seed));
+            trials.truncate(self.trials);
+        }
+        Ok(trials)
+    }
+}
+
+// swept values of a hyperparameter, or its base value alone when it is not swept
+fn or_base<T: Clone>(values: &[T], value: T) -> Vec<T> {
+    if values.is_empty() {
+        vec![value]
+    } else {
+        values.to_vec()
+    }
+}
+
+impl Trial {
+    // build, train and validate a model with this configuration
+    // dataset: dataset loaded with the dataset and validation split of the hyperparameters,
+    // shared by the trials trained at the same time
+    pub fn run(&self, dataset: &Arc<Dataset>) -> TrialResult {
+        let started = Instant::now();
+        let mut hyperparameters = self.hyperparameters.clone();
+        let outcome = Model::build_network(&mut hyperparameters, dataset)
+            .and_then(|network| {
+                Model::from_network(Arc::clone(dataset), network, hyperparameters, 0)
+            })
+            .and_then(|mut model| {
+                model.set_verbose(false);
+                model.train(self.epochs)?;
+                let accuracy = model.evaluate(CONFIG::VALIDATE).accuracy();
+                Ok((accuracy, model.get_loss()))
+            });
+        let (accuracy, loss, error) = match outcome {
+            Ok((accuracy, loss)) => (Some(accuracy), Some(loss), None),
+            Err(err) => (None, None, Some(err.to_string())),
+        };
+        TrialResult {
+            trial: self.clone(),
+            validation_accuracy: accuracy,
+            validation_loss: loss,
+            error,
+            seconds: started.elapsed().as_secs_f64(),
+        }
+    }
+}
+
+impl Sweep {
+    // train every trial in parallel on a single copy of the dataset and rank them
+    // the trials have to share the dataset and validation split, like the ones of a SweepSpec
+    // data_dir: directory holding the idx files of the dataset
+    // jobs: amount of trials trained at once, 0 uses one per cpu
+    pub fn run(trials: &[Trial], data_dir: &Path, jobs: usize) -> Result<Sweep> {
+        let Some(first) = trials.first() else {
+            return Ok(Sweep::new(Vec::new()));
+        };
+        let (kind, validation_split) = (
+            first.hyperparameters.dataset,
+            first.hyperparameters.validation_split,
+        );
+        if trials.iter().any(|trial| {
+            trial.hyperparameters.dataset != kind
+                || trial.hyperparameters.validation_split != validation_split
+        }) {
+            return Err(Error::InvalidConfig(
+                "every trial of a sweep has to use the same dataset and validation split"
+                    .to_string(),
+            ));
+        }
+        let dataset = Arc::new(Dataset::new(data_dir, kind, validation_split)?);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build()
+            .map_err(|err| Error::InvalidConfig(format!("cannot start {} jobs: {}", jobs, err)))?;
+        let results = pool.install(|| {
+            trials
+                .par_iter()
+                .enumerate()
+                .map(|(i, trial)| {
+                    let result = trial.run(&dataset);
+                    match &result.error {
+                        None => println!(
+                            "Trial {}/{}: validation accuracy {}",
+                            i + 1,
+                            trials.len(),
+                            result.validation_accuracy.unwrap_or_default()
+                        ),
+                        Some(error) => println!("Trial {}/{}: {}", i + 1, trials.len(), error),
+                    }
+                    result
+                })
+                .collect()
+        });
+        Ok(Sweep::new(results))
+    }
+
+    // rank results by validation accuracy, then by validation loss, with failed trials last
+    pub fn new(mut results: Vec<TrialResult>) -> Sweep {
+        // failed trials have neither, so they sort after every trained one
+        let accuracy =
+            |result: &TrialResult| result.validation_accuracy.unwrap_or(f32::NEG_INFINITY);
+        let loss = |result: &TrialResult| result.validation_loss.unwrap_or(f32::INFINITY);
+        results.sort_by(|a, b| {
+            accuracy(b)
+                .total_cmp(&accuracy(a))
+                .then(loss(a).total_cmp(&loss(b)))
+        });
+        Sweep { results }
+    }
+
+    // results as pretty printed json, best first
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.results).unwrap()
+    }
+
+    // results as csv, one row per trial, best first
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "rank,layers,alpha,batch_size,epochs,validation_accuracy,validation_loss,seconds,error\n",
+        );
+        let optional =
+            |value: Option<f32>| value.map(|value| value.to_string()).unwrap_or_default();
+        for (rank, result) in self.results.iter().enumerate() {
+            let hyperparameters = &result.trial.hyperparameters;
+            // layers and errors are quoted, since they can hold commas
+            csv += &format!(
+                "{},\"{}\",{},{},{},{},{},{},\"{}\"\n",
+                rank + 1,
+                layers(&hyperparameters.layers),
+                hyperparameters.alpha,
+                hyperparameters.batch_size,
+                result.trial.epochs,
+                optional(result.validation_accuracy),
+                optional(result.validation_loss),
+                result.seconds,
+                result
+                    .error
+                    .as_deref()
+                    .unwrap_or_default()
+                    .replace('"', "\"\"")
+            );
+        }
+        csv
+    }
+
+    // write the results to a file
+    pub fn export(&self, path: &Path, format: ReportFormat) -> Result<()> {
+        let contents = match format {
+            ReportFormat::Json => self.to_json(),
+            ReportFormat::Csv => self.to_csv(),
+        };
+        fs::write(path, contents).map_err(|err| Error::io(path, err))
+    }
+}
+
+// hidden layer sizes as they are given on the command line
+fn layers(sizes: &[usize]) -> String {
+    sizes
+        .iter()
+        .map(|size| size.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// table of every trial, best first
+impl fmt::Display for Sweep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<6}{:<16}{:>10}{:>12}{:>8}{:>12}{:>12}{:>10}",
+            "Rank", "Layers", "Alpha", "Batch size", "Epochs", "Accuracy", "Loss", "Seconds"
+        )?;
+        for (rank, result) in self.results.iter().enumerate() {
+            let hyperparameters = &result.trial.hyperparameters;
+            write!(
+                f,
+                "{:<6}{:<16}{:>10}{:>12}{:>8}",
+                rank + 1,
+                layers(&hyperparameters.layers),
+                hyperparameters.alpha,
+                hyperparameters.batch_size,
+                result.trial.epochs
+            )?;
+            match (
+                result.validation_accuracy,
+                result.validation_loss,
+                &result.error,
+            ) {
+                (Some(accuracy), Some(loss), _) => writeln!(
+                    f,
+                    "{:>12.4}{:>12.4}{:>10.1}",
+                    accuracy, loss, result.seconds
+                )?,
+                (_, _, error) => writeln!(f, "  {}", error.as_deref().unwrap_or_default())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> Hyperparameters {
+        Hyperparameters {
+            validation_split: 0.1,
+            seed: Some(1),
+            ..Hyperparameters::default()
+        }
+    }
+
+    #[test]
+    fn grid_search_tries_every_combination() {
+        let spec = SweepSpec {
+            layers: vec![vec![32], vec![64, 32]],
+            alpha: vec![0.1, 0.01, 0.001],
+            ..SweepSpec::default()
+        };
+        let trials = spec.trials(&base(), 7).unwrap();
+        assert_eq!(trials.len(), 6);
+        assert_eq!(trials[1].hyperparameters.layers, vec![32]);
+        assert_eq!(trials[1].hyperparameters.alpha, 0.01);
+        // values that are not swept come from the base hyperparameters
+        assert!(trials.iter().all(|trial| trial.epochs == 7
+            && trial.hyperparameters.batch_size == base().batch_size
+            && trial.hyperparameters.seed == Some(1)));
+
+        let unswept = Hyperparameters {
+            validation_split: 0.0,
+            ..base()
+        };
+        assert!(spec.trials(&unswept, 7).is_err());
+    }
+
+    #[test]
+    fn random_search_picks_distinct_combinations_from_the_seed() {
+        let spec = SweepSpec {
+            search: Search::Random,
+            trials: 4,
+            alpha: vec![0.1, 0.01, 0.001],
+            batch_size: vec![10, 50, 100],
+            ..SweepSpec::default()
+        };
+        let picked = |seed| {
+            let base = Hyperparameters {
+                seed: Some(seed),
+                ..base()
+            };
+            spec.trials(&base, 1)
+                .unwrap()
+                .iter()
+                .map(|trial| {
+                    (
+                        trial.hyperparameters.alpha.to_bits(),
+                        trial.hyperparameters.batch_size,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let trials = picked(2);
+        assert_eq!(trials.len(), 4);
+        assert!((1..4).all(|i| !trials[..i].contains(&trials[i])));
+        assert_eq!(trials, picked(2));
+    }
+
+    #[test]
+    fn results_are_ranked_by_accuracy_then_loss() {
+        let result = |accuracy: Option<f32>, loss: Option<f32>| TrialResult {
+            trial: Trial {
+                hyperparameters: base(),
+                epochs: 1,
+            },
+            validation_accuracy: accuracy,
+            validation_loss: loss,
+            error: accuracy.is_none().then(|| "diverged".to_string()),
+            seconds: 0.0,
+        };
+        let sweep = Sweep::new(vec![
+            result(None, None),
+            result(Some(0.8), Some(0.5)),
+            result(Some(0.9), Some(0.7)),
+            result(Some(0.8), Some(0.4)),
+        ]);
+        let ranked: Vec<_> = sweep
+            .results
+            .iter()
+            .map(|result| (result.validation_accuracy, result.validation_loss))
+            .collect();
+        assert_eq!(
+            ranked,
+            vec![
+                (Some(0.9), Some(0.7)),
+                (Some(0.8), Some(0.4)),
+                (Some(0.8), Some(0.5)),
+                (None, None)
+            ]
+        );
+    }
+}